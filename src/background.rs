@@ -0,0 +1,66 @@
+use std::sync::Arc;
+
+use serde::Deserialize;
+
+use crate::{lights::dir_to_equirect_uv, math::*, raytracing::ObjectPoint, spectrum::*, textures::*};
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum BackgroundParameters {
+  Constant(ColorParameters),
+  Gradient { bottom: ColorParameters, top: ColorParameters },
+  Texture { texture: Box<dyn TextureParameters> }
+}
+
+impl BackgroundParameters {
+  pub fn build_background(&self) -> Background {
+    match self {
+      BackgroundParameters::Constant(color) => Background::Constant(color.build_color()),
+      BackgroundParameters::Gradient { bottom, top } => {
+        Background::Gradient { bottom: bottom.build_color(), top: top.build_color() }
+      },
+      BackgroundParameters::Texture { texture } => Background::Texture(texture.build_texture())
+    }
+  }
+}
+
+/// The radiance arriving along rays which escape the scene, on top of that of any light sources.
+#[derive(Debug)]
+pub enum Background {
+  Constant(Spectrum),
+
+  /// Blends linearly between two colors from straight down (-y) to straight up (+y)
+  Gradient {
+    bottom: Spectrum,
+    top: Spectrum
+  },
+
+  /// Looks up a texture by direction, using the same equirectangular mapping as environment maps
+  Texture(Arc<dyn Texture>)
+}
+
+impl Default for Background {
+  fn default() -> Self { Background::Constant(Spectrum::none()) }
+}
+
+impl Background {
+  pub fn radiance(&self, dir: &WorldUnitVector) -> Spectrum {
+    let d = dir.into_vector();
+    match self {
+      Background::Constant(color) => *color,
+      Background::Gradient { bottom, top } => {
+        let t = (d[1] + 1.0) / 2.0;
+        *bottom * (1.0 - t) + *top * t
+      },
+      Background::Texture(texture) => {
+        let (u, v) = dir_to_equirect_uv(dir);
+        // Solid textures are evaluated on the unit sphere of directions
+        texture.value(&TexturePoint {
+          tex_coord: TextureCoordinate::from_array([u, v]),
+          object_point: ObjectPoint::from_array([d[0], d[1], d[2]]),
+          footprint: TextureFootprint::point()
+        })
+      }
+    }
+  }
+}
@@ -64,19 +64,29 @@ pub struct Camera {
 }
 
 impl Camera {
-  pub fn sample_ray_through_pixel(&self, sampler: &mut dyn Sampler, mut u: Real, mut v: Real) -> WorldRay {
-    u /= self.resolution.0 as Real;
-    v /= self.resolution.1 as Real;
-
+  /// Samples a ray through the point (`u`, `v`) in pixel coordinates, along with differentials through the points one
+  /// pixel to the right and one pixel down which leave the same point on the lens.
+  pub fn sample_ray_through_pixel(&self, sampler: &mut dyn Sampler, u: Real, v: Real) -> WorldRay {
     let disc = (uniform_random_in_unit_disc(sampler) * self.aperture_radius).into_inner();
     let origin = Point::from(nalgebra::point![disc.x, disc.y, 0.0]);
-    let dir = Point::from(nalgebra::point![
-      (u - 0.5) * self.image_plane_size.0,
-      (0.5 - v) * self.image_plane_size.1,
-      -self.focal_distance
-    ]) - origin;
-
-    self.transform.inverse_ray(&Ray::new(origin, dir.normalize()))
+    let dir_through = |u: Real, v: Real| {
+      let (u, v) = (u / self.resolution.0 as Real, v / self.resolution.1 as Real);
+      (Point::from(nalgebra::point![
+        (u - 0.5) * self.image_plane_size.0,
+        (0.5 - v) * self.image_plane_size.1,
+        -self.focal_distance
+      ]) - origin)
+        .normalize()
+    };
+
+    let differentials = RayDifferentials {
+      x_origin: origin,
+      x_dir: dir_through(u + 1.0, v),
+      y_origin: origin,
+      y_dir: dir_through(u, v + 1.0)
+    };
+    let ray = Ray::new(origin, dir_through(u, v)).with_kind(RayKind::Camera).with_differentials(Some(differentials));
+    self.transform.inverse_ray(&ray)
   }
 
   pub fn resolution(&self) -> (u32, u32) { self.resolution }
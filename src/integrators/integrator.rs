@@ -1,6 +1,14 @@
 use std::{error::Error, fmt::Debug};
 
-use crate::{math::PositiveReal, raytracing::*, sampling::Sampler, scene::Scene, spectrum::*, BuildSettings};
+use crate::{
+  math::{PositiveReal, Real},
+  raytracing::*,
+  sampling::Sampler,
+  scene::Scene,
+  spectrum::*,
+  surfaces::Visibility,
+  BuildSettings
+};
 
 #[typetag::deserialize(tag = "type")]
 pub trait IntegratorParameters: Debug {
@@ -12,43 +20,106 @@ pub trait PathTraceIntegratorParameters: IntegratorParameters {}
 
 pub trait Integrator: Send + Sync {
   fn radiance_estimate(&self, sampler: &mut dyn Sampler, ray: WorldRay) -> Spectrum;
+
+  /// Estimates the radiance along `ray` at each of the given wavelengths, which by default treats the RGB estimate as
+  /// the color of a light.
+  fn spectral_radiance_estimate(
+    &self,
+    sampler: &mut dyn Sampler,
+    ray: WorldRay,
+    wavelengths: &mut SampledWavelengths
+  ) -> SampledSpectrum {
+    wavelengths.illuminant(&self.radiance_estimate(sampler, ray))
+  }
+}
+
+/// The ray scattered from a vertex of a path, along with everything needed to weight it.
+pub struct Scatter<'a, S = Spectrum> {
+  /// The radiance leaving the vertex back along the path, which is not attenuated by this scattering event
+  pub emitted: S,
+  pub attenuation: S,
+  pub ray: WorldRay,
+  pub pdf: PositiveReal,
+
+  /// The solid-angle density with which the direction of `ray` was sampled, if light was also sampled directly at the
+  /// vertex (so that whatever light `ray` finds must be weighted against that strategy)
+  pub mis_pdf: Option<PositiveReal>,
+
+  /// The surface which `ray` leaves, whose light links decide which lights the ray can find
+  pub source: Option<&'a Visibility>
+}
+
+/// Veach's power heuristic (with an exponent of two) for weighting a sample drawn with density `pdf` against another
+/// strategy which would have drawn it with density `other_pdf`.
+pub fn power_heuristic(pdf: Real, other_pdf: Real) -> Real {
+  let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+  if a + b > 0.0 {
+    a / (a + b)
+  } else {
+    0.0
+  }
 }
 
 pub trait PathTraceIntegrator {
   fn initial_path_terminator(&self, ray: WorldRay) -> PathTerminator;
 
-  /// Returns Ok((emitted, attenuation, scattered_ray, maybe_pdf)) or Err(final_estimate)
-  fn sample_scatter(
-    &self,
+  /// Returns Ok(scatter) or Err(final_estimate), where `mis_pdf` and `source` are those of the scatter which produced
+  /// `ray` (or `None` for rays leaving the camera). Colors are turned into the values the path carries by
+  /// `wavelengths`.
+  fn sample_scatter<'a, W: Wavelengths>(
+    &'a self,
+    wavelengths: &mut W,
     sampler: &mut dyn Sampler,
-    ray: WorldRay
-  ) -> Result<(Spectrum, Spectrum, WorldRay, Option<PositiveReal>), Spectrum>;
+    ray: WorldRay,
+    mis_pdf: Option<PositiveReal>,
+    source: Option<&'a Visibility>
+  ) -> Result<Scatter<'a, W::Spectrum>, W::Spectrum>;
 }
 
-impl<T: PathTraceIntegrator + Send + Sync> Integrator for T {
-  fn radiance_estimate(&self, sampler: &mut dyn Sampler, ray: WorldRay) -> Spectrum {
-    let mut terminator = self.initial_path_terminator(ray);
-    let mut total_path_attenuation = Spectrum::white();
-    let mut radiance = Spectrum::none();
-
-    while let Some((ray, survival_probability, cont)) = terminator.into_ray(sampler) {
-      match self.sample_scatter(sampler, ray) {
-        Ok((emitted, attenuation, scattered_ray, maybe_pdf)) => {
-          radiance += total_path_attenuation * emitted;
-          total_path_attenuation *= attenuation / survival_probability;
-          if let Some(sample_pdf) = maybe_pdf {
-            total_path_attenuation /= sample_pdf.into_inner();
-          }
-
-          terminator = cont.into_terminator(scattered_ray);
-        },
-        Err(final_radiance) => {
-          radiance += total_path_attenuation * final_radiance;
-          break;
-        }
+fn trace_path<T: PathTraceIntegrator, W: Wavelengths>(
+  integrator: &T,
+  wavelengths: &mut W,
+  sampler: &mut dyn Sampler,
+  ray: WorldRay
+) -> W::Spectrum {
+  let mut terminator = integrator.initial_path_terminator(ray);
+  let mut total_path_attenuation = W::Spectrum::white();
+  let mut radiance = W::Spectrum::none();
+
+  let mut mis_pdf = None;
+  let mut source = None;
+
+  while let Some((ray, survival_probability, cont)) = terminator.into_ray(sampler) {
+    match integrator.sample_scatter(wavelengths, sampler, ray, mis_pdf, source) {
+      Ok(scatter) => {
+        radiance += total_path_attenuation * scatter.emitted;
+        total_path_attenuation *= scatter.attenuation / (survival_probability * scatter.pdf.into_inner());
+
+        mis_pdf = scatter.mis_pdf;
+        source = scatter.source;
+        terminator = cont.into_terminator(scatter.ray);
+      },
+      Err(final_radiance) => {
+        radiance += total_path_attenuation * final_radiance;
+        break;
       }
     }
+  }
+
+  radiance
+}
 
-    radiance
+impl<T: PathTraceIntegrator + Send + Sync> Integrator for T {
+  fn radiance_estimate(&self, sampler: &mut dyn Sampler, ray: WorldRay) -> Spectrum {
+    trace_path(self, &mut RgbWavelengths, sampler, ray)
+  }
+
+  fn spectral_radiance_estimate(
+    &self,
+    sampler: &mut dyn Sampler,
+    ray: WorldRay,
+    wavelengths: &mut SampledWavelengths
+  ) -> SampledSpectrum {
+    trace_path(self, wavelengths, sampler, ray)
   }
 }
@@ -4,16 +4,16 @@ use serde::Deserialize;
 
 use super::*;
 use crate::{
-  materials::ScatterRandomVariable,
-  math::{PositiveReal, Real},
+  materials::{MediumEvent, TransportMode},
+  math::{PositiveReal, Real, VectorLike, WorldPoint, WorldUnitVector},
   raytracing::*,
   sampling::*,
   scene::Scene,
   spectrum::*,
+  surfaces::Visibility,
   BuildSettings
 };
 
-// TODO: Add background to this
 #[derive(Debug, Deserialize)]
 struct Parameters {
   #[serde(alias = "average-path-length")]
@@ -25,16 +25,115 @@ impl IntegratorParameters for Parameters {
   fn build_integrator(&self, scene: Scene, _: BuildSettings) -> Result<Box<dyn Integrator>, Box<dyn Error>> {
     Ok(Box::new(MaterialPathTracer {
       scene,
-      path_termination_probability: PositiveReal::new_unchecked(1.0 / (self.average_path_length as Real)),
-      background: Spectrum::none()
+      path_termination_probability: PositiveReal::new_unchecked(1.0 / (self.average_path_length as Real))
     }))
   }
 }
 
 pub struct MaterialPathTracer {
   scene: Scene,
-  path_termination_probability: PositiveReal,
-  background: Spectrum
+  path_termination_probability: PositiveReal
+}
+
+impl MaterialPathTracer {
+  /// The number of lights to choose between when sampling one, where all of the emissive surfaces count as one.
+  fn num_lights(&self) -> usize {
+    let has_emitters = self.scene.emissive_part().emitter_summary().count > 0;
+    self.scene.light_sources().len() + usize::from(has_emitters)
+  }
+
+  /// The pdf of choosing to sample the emissive surfaces and then sampling direction `dir` from them at `point`.
+  fn emitter_pdf(&self, point: &WorldPoint, dir: &WorldUnitVector) -> Real {
+    let pdf = self.scene.emissive_part().random_intersecting_direction().pdf(point, dir);
+    pdf.map_or(0.0, PositiveReal::into_inner) / self.num_lights() as Real
+  }
+
+  /// Estimates the light arriving at `hit` directly from either one of the scene's light sources or its emissive
+  /// surfaces (chosen uniformly) and scattered towards `out_dir`. Delta light sources and emitters hidden from
+  /// reflections can only ever be reached this way, while the rest are weighted against finding them by sampling the
+  /// BSDF.
+  fn sample_light_source<W: Wavelengths>(
+    &self,
+    wavelengths: &W,
+    hit: &WorldSurfaceInterface,
+    out_dir: &WorldUnitVector,
+    sampler: &mut dyn Sampler
+  ) -> W::Spectrum {
+    let point = &hit.surface_point;
+    let light_sources = self.scene.light_sources();
+    let num_lights = self.num_lights();
+    if num_lights == 0 {
+      return W::Spectrum::none();
+    }
+
+    let index = ((sampler.next().into_inner() * num_lights as Real) as usize).min(num_lights - 1);
+    let (dir, radiance, pdf, is_weighted) = if let Some(light_source) = light_sources.get(index) {
+      if !hit.visibility.is_lit_by(self.scene.light_source_name(index)) {
+        return W::Spectrum::none();
+      }
+
+      match light_source.sample_incident(&point.point, sampler) {
+        Some(sample) if self.scene.unoccluded(point.point, sample.dir, sample.dist) => {
+          (sample.dir, sample.radiance, sample.pdf, !sample.is_delta)
+        },
+        _ => return W::Spectrum::none()
+      }
+    } else {
+      // Whatever the sampled direction actually reaches first is what gets lit, as the pdf accounts for all of it
+      let emissive_part = self.scene.emissive_part().random_intersecting_direction();
+      match emissive_part.sample_with_pdf(&point.point, sampler) {
+        Some((dir, pdf)) => match self.scene.intersect_world_ray(Ray::new(point.point, dir).with_kind(RayKind::Shadow))
+        {
+          Some(light_hit) if hit.visibility.is_lit_by(light_hit.visibility.light()) => {
+            let radiance = light_hit.light.radiance_emitted(&light_hit.surface_point, &-dir);
+            (dir, radiance, pdf, light_hit.visibility.is_visible_to(RayKind::Reflection))
+          },
+          _ => return W::Spectrum::none()
+        },
+        None => return W::Spectrum::none()
+      }
+    };
+
+    let bsdf_cos = hit.material.bsdf_cos(point, &dir, out_dir, TransportMode::Radiance);
+    let light_pdf = pdf.into_inner() / num_lights as Real;
+    let weight = if is_weighted {
+      let bsdf_pdf = hit.material.bsdf_pdf(point, &dir, out_dir).map_or(0.0, PositiveReal::into_inner);
+      power_heuristic(light_pdf, bsdf_pdf)
+    } else {
+      1.0
+    };
+
+    wavelengths.reflectance(&bsdf_cos) * wavelengths.illuminant(&radiance) * (weight / light_pdf)
+  }
+
+  /// The radiance arriving along a ray from `origin` in direction `dir` which escapes the scene, from the light sources
+  /// which light the surface it left (if any).
+  fn radiance_escaped(
+    &self,
+    origin: &WorldPoint,
+    dir: &WorldUnitVector,
+    mis_pdf: Option<PositiveReal>,
+    source: Option<&Visibility>
+  ) -> Spectrum {
+    let light_sources = self.scene.light_sources();
+    let mut radiance = self.scene.background().radiance(dir);
+    for (index, light_source) in light_sources.iter().enumerate() {
+      if source.is_some_and(|s| !s.is_lit_by(self.scene.light_source_name(index))) {
+        continue;
+      }
+
+      let weight = match (mis_pdf, light_source.incident_pdf(origin, dir)) {
+        (Some(bsdf_pdf), Some(light_pdf)) => {
+          power_heuristic(bsdf_pdf.into_inner(), light_pdf.into_inner() / self.num_lights() as Real)
+        },
+        _ => 1.0
+      };
+
+      radiance += light_source.radiance_escaped(dir) * weight;
+    }
+
+    radiance
+  }
 }
 
 impl PathTraceIntegrator for MaterialPathTracer {
@@ -42,42 +141,100 @@ impl PathTraceIntegrator for MaterialPathTracer {
     PathTerminator::new(ray, self.path_termination_probability)
   }
 
-  fn sample_scatter(
-    &self,
+  fn sample_scatter<'a, W: Wavelengths>(
+    &'a self,
+    wavelengths: &mut W,
     sampler: &mut dyn Sampler,
-    ray: WorldRay
-  ) -> Result<(Spectrum, Spectrum, WorldRay, Option<PositiveReal>), Spectrum> {
-    let out_dir = -ray.dir();
+    ray: WorldRay,
+    mis_pdf: Option<PositiveReal>,
+    source: Option<&'a Visibility>
+  ) -> Result<Scatter<'a, W::Spectrum>, W::Spectrum> {
+    let (origin, dir, differentials) = (ray.origin(), ray.dir(), ray.differentials().copied());
+    let out_dir = -dir;
     if let Some(hit) = self.scene.intersect_world_ray(ray) {
-      let radiance_emitted = hit.light.radiance_emitted(&hit.surface_point, &out_dir);
-      let param = (hit.surface_point, out_dir);
-
-      match hit.material.random_bsdf_in_direction() {
-        ScatterRandomVariable::Diffuse(rv) => {
-          if let Some((in_dir, pdf)) = rv.sample_with_pdf(&param, sampler) {
-            return Ok((
-              radiance_emitted,
-              hit.material.bsdf_cos(&param.0, &in_dir, &out_dir),
-              Ray::new(param.0.point, in_dir),
-              Some(pdf)
-            ));
+      // A ray leaving a surface through its back has been travelling through whatever the surface encloses
+      let mut transmittance = W::Spectrum::white();
+      let mut pass_probability = 1.0;
+      if dir.dot(&hit.surface_point.geometric_normal) > 0.0 {
+        if let Some(medium) = hit.material.interior(&hit.surface_point) {
+          match medium.sample_event(hit.intersect_dist.into_inner(), sampler).ok_or(W::Spectrum::none())? {
+            MediumEvent::Scatter { dist, weight, pdf } => {
+              let (scattered_dir, phase) = medium.sample_phase(&dir, sampler).ok_or(W::Spectrum::none())?;
+              let scattered_ray = Ray::new(origin + dir * dist, scattered_dir);
+              let pdf = PositiveReal::new_unchecked(pdf.into_inner() * phase.into_inner());
+              return Ok(Scatter {
+                emitted: W::Spectrum::none(),
+                attenuation: wavelengths.reflectance(&weight) * phase.into_inner(),
+                ray: scattered_ray,
+                pdf,
+                mis_pdf: None,
+                source: None
+              });
+            },
+            MediumEvent::Pass { weight, probability } => {
+              transmittance = wavelengths.reflectance(&weight);
+              pass_probability = probability.into_inner();
+            }
           }
-        },
-        ScatterRandomVariable::Specular(rv) => {
-          if let Some(in_dir) = rv.sample(&param, sampler) {
-            return Ok((
-              radiance_emitted,
-              hit.material.bsdf_cos(&param.0, &in_dir, &out_dir),
-              Ray::new(param.0.point, in_dir),
-              None
-            ));
-          }
-        },
+        }
+      }
+
+      // Emitters only light the surfaces linked to them. Light emitted towards a BSDF-sampled ray could have been found
+      // by sampling the emissive surfaces instead, unless shadow rays can't see the emitter.
+      let mut radiance_emitted = if source.is_none_or(|s| s.is_lit_by(hit.visibility.light())) {
+        hit.light.radiance_emitted(&hit.surface_point, &out_dir)
+      } else {
+        Spectrum::none()
+      };
+
+      let can_be_sampled = hit.visibility.is_visible_to(RayKind::Shadow);
+      if let Some(bsdf_pdf) = mis_pdf.filter(|_| can_be_sampled && radiance_emitted.luminance() > 0.0) {
+        radiance_emitted *= power_heuristic(bsdf_pdf.into_inner(), self.emitter_pdf(&origin, &dir));
       }
 
-      Err(radiance_emitted)
+      let radiance_emitted = (wavelengths.illuminant(&radiance_emitted)
+        + self.sample_light_source(wavelengths, &hit, &out_dir, sampler))
+        * transmittance
+        / pass_probability;
+
+      // Light leaving a dispersive material in the sampled direction only has the hero wavelength
+      let (maybe_sample, dispersion) = match wavelengths.hero().filter(|_| hit.material.is_dispersive()) {
+        Some(wavelength) => (
+          hit.material.sample_bsdf_at_wavelength(
+            &hit.surface_point,
+            &out_dir,
+            TransportMode::Radiance,
+            wavelength,
+            sampler
+          ),
+          wavelengths.terminate_secondary()
+        ),
+        None => (
+          hit.material.sample_bsdf(&hit.surface_point, &out_dir, TransportMode::Radiance, sampler),
+          W::Spectrum::white()
+        )
+      };
+
+      match maybe_sample {
+        Some(sample) => {
+          // Only specular bounces keep the footprint of the pixel coherent enough to be worth following
+          let differentials = differentials
+            .filter(|_| sample.is_delta)
+            .and_then(|d| hit.surface_point.scatter_differentials(&d, &out_dir, &sample.in_dir));
+          let pdf = PositiveReal::new_unchecked(sample.pdf.into_inner() * pass_probability);
+          Ok(Scatter {
+            emitted: radiance_emitted,
+            attenuation: wavelengths.reflectance(&sample.bsdf_cos) * transmittance * dispersion,
+            ray: Ray::new(hit.surface_point.point, sample.in_dir).with_differentials(differentials),
+            pdf,
+            mis_pdf: (!sample.is_delta).then_some(sample.pdf),
+            source: Some(hit.visibility)
+          })
+        },
+        None => Err(radiance_emitted)
+      }
     } else {
-      Err(self.background)
+      Err(wavelengths.illuminant(&self.radiance_escaped(&origin, &dir, mis_pdf, source)))
     }
   }
 }
@@ -3,9 +3,7 @@ use std::error::Error;
 use serde::Deserialize;
 
 use super::*;
-use crate::{
-  materials::ScatterRandomVariable, raytracing::*, sampling::Sampler, scene::Scene, spectrum::*, BuildSettings
-};
+use crate::{materials::TransportMode, raytracing::*, sampling::Sampler, scene::Scene, spectrum::*, BuildSettings};
 
 #[derive(Debug, Deserialize)]
 pub struct NormalIntegratorParameters;
@@ -23,27 +21,18 @@ struct NormalIntegrator {
 
 impl Integrator for NormalIntegrator {
   fn radiance_estimate(&self, sampler: &mut dyn Sampler, ray: WorldRay) -> Spectrum {
-    let out_dir = -ray.dir();
+    let dir = ray.dir();
+    let out_dir = -dir;
     if let Some(hit) = self.scene.intersect_world_ray(ray) {
       let mut radiance_emitted = hit.light.radiance_emitted(&hit.surface_point, &out_dir);
-      let param = (hit.surface_point, out_dir);
-
-      match hit.material.random_bsdf_in_direction() {
-        ScatterRandomVariable::Diffuse(rv) => {
-          if let Some((in_dir, _)) = rv.sample_with_pdf(&param, sampler) {
-            radiance_emitted += hit.material.bsdf_cos(&param.0, &in_dir, &out_dir);
-          }
-        },
-        ScatterRandomVariable::Specular(rv) => {
-          if let Some(in_dir) = rv.sample(&param, sampler) {
-            radiance_emitted += hit.material.bsdf_cos(&param.0, &in_dir, &out_dir);
-          }
-        },
+      if let Some(sample) = hit.material.sample_bsdf(&hit.surface_point, &out_dir, TransportMode::Radiance, sampler) {
+        // Delta lobes are shown at their full weight rather than scaled by the chance of choosing them
+        radiance_emitted += if sample.is_delta { sample.bsdf_cos / sample.pdf.into_inner() } else { sample.bsdf_cos };
       }
 
       radiance_emitted
     } else {
-      Spectrum::none()
+      self.scene.radiance_escaped(&dir)
     }
   }
 }
@@ -11,27 +11,72 @@ use crate::{
   textures::{Texture, TextureParameters}
 };
 
+fn default_intensity() -> Real { 1.0 }
+
+fn default_two_sided() -> bool { true }
+
 #[derive(Debug, Deserialize)]
 struct DiffuseLightParameters {
   name: String,
-  emitted_radiance: Box<dyn TextureParameters>
+
+  #[serde(alias = "emit")]
+  emitted_radiance: Box<dyn TextureParameters>,
+
+  #[serde(default = "default_intensity")]
+  intensity: Real,
+
+  #[serde(alias = "two-sided", default = "default_two_sided")]
+  two_sided: bool,
+
+  /// The total power in watts emitted by each surface using this light, which replaces `intensity` when given
+  power: Option<Real>
 }
 
 #[typetag::deserialize(name = "diffuse light")]
 impl LightParameters for DiffuseLightParameters {
   fn name(&self) -> String { self.name.clone() }
 
-  fn build_light(&self) -> Arc<dyn Light> { Arc::new(DiffuseLight { emitted: self.emitted_radiance.build_texture() }) }
+  fn build_light(&self) -> Arc<dyn Light> {
+    Arc::new(DiffuseLight {
+      emitted: self.emitted_radiance.build_texture(),
+      scale: self.intensity,
+      two_sided: self.two_sided,
+      power: self.power
+    })
+  }
 }
 
+/// Emits the radiance given by a texture (times a scale factor) uniformly in all directions from the front of a
+/// surface, and also from its back if it is two-sided. When the light is specified by its power, the texture gives only
+/// the color and should have an average luminance of one.
 #[derive(Debug)]
 pub struct DiffuseLight {
-  emitted: Arc<dyn Texture>
+  emitted: Arc<dyn Texture>,
+  scale: Real,
+  two_sided: bool,
+  power: Option<Real>
 }
 
 impl Light for DiffuseLight {
-  fn radiance_emitted(&self, emit_point: &WorldSurfacePoint, _: &WorldUnitVector) -> Spectrum {
-    self.emitted.value(&emit_point.tex_coord)
+  fn radiance_emitted(&self, emit_point: &WorldSurfacePoint, emit_dir: &WorldUnitVector) -> Spectrum {
+    if !self.two_sided && emit_dir.dot(&emit_point.geometric_normal) <= 0.0 {
+      return Spectrum::none();
+    }
+
+    self.emitted.value(&emit_point.texture_point()) * self.scale
+  }
+
+  fn with_surface_area(&self, area: PositiveReal) -> Option<Arc<dyn Light>> {
+    // A Lambertian emitter of radiance L emits a power of pi * L per unit area from each side
+    let sides = if self.two_sided { 2.0 } else { 1.0 };
+    self.power.map(|power| {
+      Arc::new(DiffuseLight {
+        emitted: self.emitted.clone(),
+        scale: power / (sides * PI * area.into_inner()),
+        two_sided: self.two_sided,
+        power: None
+      }) as Arc<dyn Light>
+    })
   }
 
   fn random_emit_direction(
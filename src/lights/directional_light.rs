@@ -0,0 +1,44 @@
+use serde::Deserialize;
+
+use super::*;
+use crate::{math::*, sampling::Sampler, spectrum::*};
+
+#[derive(Debug, Deserialize)]
+struct DirectionalLightParameters {
+  name: Option<String>,
+
+  /// The direction in which the light travels
+  direction: [Real; 3],
+  irradiance: ColorParameters
+}
+
+#[typetag::deserialize(name = "directional")]
+impl LightSourceParameters for DirectionalLightParameters {
+  fn name(&self) -> Option<String> { self.name.clone() }
+
+  fn build_light_source(&self) -> Box<dyn LightSource> {
+    Box::new(DirectionalLight {
+      to_light: -UnitVector::from_array(self.direction),
+      irradiance: self.irradiance.build_color()
+    })
+  }
+}
+
+/// Light arriving from a single direction infinitely far away, such as a distant sun.
+#[derive(Debug)]
+pub struct DirectionalLight {
+  to_light: WorldUnitVector,
+  irradiance: Spectrum
+}
+
+impl LightSource for DirectionalLight {
+  fn sample_incident(&self, _: &WorldPoint, _: &mut dyn Sampler) -> Option<LightSourceSample> {
+    Some(LightSourceSample {
+      dir: self.to_light,
+      dist: None,
+      radiance: self.irradiance,
+      pdf: PositiveReal::ONE,
+      is_delta: true
+    })
+  }
+}
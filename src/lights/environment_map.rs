@@ -0,0 +1,140 @@
+use image::{io::Reader, ImageBuffer, Rgb};
+use serde::Deserialize;
+
+use super::*;
+use crate::{
+  math::*,
+  sampling::*,
+  spectrum::{working_space, ColorSpace, Spectrum}
+};
+
+fn default_intensity() -> Real { 1.0 }
+
+#[derive(Debug, Deserialize)]
+struct EnvironmentMapParameters {
+  name: Option<String>,
+
+  filename: String,
+
+  #[serde(default = "default_intensity")]
+  intensity: Real,
+  transform: Option<TransformParameters>,
+
+  /// The (linear) color space of the image's pixels
+  #[serde(alias = "color-space", default)]
+  color_space: ColorSpace
+}
+
+#[typetag::deserialize(name = "envmap")]
+impl LightSourceParameters for EnvironmentMapParameters {
+  fn name(&self) -> Option<String> { self.name.clone() }
+
+  fn build_light_source(&self) -> Box<dyn LightSource> {
+    println!("Loading environment map from \"{}\"...", self.filename);
+    let mut image = Reader::open(&self.filename)
+      .expect("Environment map file not found!")
+      .decode()
+      .expect("Environment map could not be decoded!")
+      .into_rgb32f();
+
+    let working_space = working_space();
+    if self.color_space != working_space {
+      for pixel in image.pixels_mut() {
+        let color = self.color_space.convert(&Spectrum::new(pixel[0], pixel[1], pixel[2]), working_space);
+        *pixel = Rgb([color.r(), color.g(), color.b()]);
+      }
+    }
+
+    let transform = self.transform.clone().unwrap_or(TransformParameters::Composed(Vec::new())).build_transform();
+    Box::new(EnvironmentMap::new(image, self.intensity, transform))
+  }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct EnvironmentSpace;
+
+impl Space<3> for EnvironmentSpace {}
+
+/// The direction at a point of an equirectangular image (see `EnvironmentMap`), along with the sine of its polar angle.
+pub(super) fn equirectangular_direction(u: Real, v: Real) -> (UnitVector3<EnvironmentSpace>, Real) {
+  let (theta, phi) = (v * PI, (u - 0.5) * 2.0 * PI);
+  let sin_theta = theta.sin();
+  (UnitVector3::from_array([sin_theta * phi.sin(), theta.cos(), -sin_theta * phi.cos()]), sin_theta)
+}
+
+/// The point of an equirectangular image in direction `dir`, the inverse of `equirectangular_direction`.
+pub fn dir_to_equirect_uv<S: Space<3>>(dir: &UnitVector3<S>) -> (Real, Real) {
+  let d = dir.into_vector();
+  let u = 0.5 + d[0].atan2(-d[2]) * INV_PI / 2.0;
+  let v = d[1].clamp(-1.0, 1.0).acos() * INV_PI;
+  (u, v)
+}
+
+/// Light arriving from infinitely far away in every direction, given by an equirectangular image whose top row is the
+/// +y direction and whose center is the -z direction (before the map's own transform is applied).
+#[derive(Debug)]
+pub struct EnvironmentMap {
+  image: ImageBuffer<Rgb<f32>, Vec<f32>>,
+  intensity: Real,
+  distribution: PiecewiseConstant2D,
+  transform: LocalToWorld<EnvironmentSpace>
+}
+
+impl EnvironmentMap {
+  pub(super) fn new(
+    image: ImageBuffer<Rgb<f32>, Vec<f32>>,
+    intensity: Real,
+    transform: LocalToWorld<EnvironmentSpace>
+  ) -> Self {
+    // Weight each pixel by the solid angle it covers, which shrinks towards the poles
+    let (width, height) = image.dimensions();
+    let weights: Vec<_> = image
+      .enumerate_pixels()
+      .map(|(_, y, p)| {
+        let sin_theta = (PI * (y as Real + 0.5) / height as Real).sin();
+        Spectrum::new(p[0], p[1], p[2]).luminance() * sin_theta
+      })
+      .collect();
+
+    Self { image, intensity, distribution: PiecewiseConstant2D::new(&weights, width as usize), transform }
+  }
+
+  fn dir_to_uv(&self, dir: &WorldUnitVector) -> (Real, Real) {
+    let (u, v) = dir_to_equirect_uv(&self.transform.inverse_direction(dir));
+    (u.clamp(0.0, 1.0), v.clamp(0.0, 1.0))
+  }
+
+  fn lookup(&self, (u, v): (Real, Real)) -> Spectrum {
+    let (width, height) = self.image.dimensions();
+    let x = ((u * width as Real) as u32).min(width - 1);
+    let y = ((v * height as Real) as u32).min(height - 1);
+    let p = self.image.get_pixel(x, y);
+    Spectrum::new(p[0], p[1], p[2]) * self.intensity
+  }
+
+  /// Converts a density over the image into a density over solid angle, given the sine of the polar angle.
+  fn solid_angle_pdf(pdf: PositiveReal, sin_theta: Real) -> Option<PositiveReal> {
+    PositiveReal::new(pdf.into_inner() / (2.0 * PI * PI * sin_theta))
+  }
+}
+
+impl LightSource for EnvironmentMap {
+  fn sample_incident(&self, _: &WorldPoint, sampler: &mut dyn Sampler) -> Option<LightSourceSample> {
+    let ((u, v), pdf) = self.distribution.sample_with_pdf(&(), sampler)?;
+    let (local, sin_theta) = equirectangular_direction(u, v);
+    Some(LightSourceSample {
+      dir: self.transform.direction(&local),
+      dist: None,
+      radiance: self.lookup((u, v)),
+      pdf: Self::solid_angle_pdf(pdf, sin_theta)?,
+      is_delta: false
+    })
+  }
+
+  fn incident_pdf(&self, _: &WorldPoint, dir: &WorldUnitVector) -> Option<PositiveReal> {
+    let uv = self.dir_to_uv(dir);
+    Self::solid_angle_pdf(self.distribution.pdf(&(), &uv)?, (uv.1 * PI).sin())
+  }
+
+  fn radiance_escaped(&self, dir: &WorldUnitVector) -> Spectrum { self.lookup(self.dir_to_uv(dir)) }
+}
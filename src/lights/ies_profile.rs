@@ -0,0 +1,176 @@
+use std::fs;
+
+use serde::Deserialize;
+
+use crate::math::*;
+
+#[derive(Debug, Clone, Deserialize)]
+pub(super) struct IesProfileParameters {
+  filename: String,
+  transform: Option<TransformParameters>
+}
+
+impl IesProfileParameters {
+  pub(super) fn build_profile(&self) -> IesProfile {
+    println!("Loading IES profile from \"{}\"...", self.filename);
+    let contents = fs::read_to_string(&self.filename).expect("IES profile file not found!");
+    let transform = self.transform.clone().unwrap_or(TransformParameters::Composed(Vec::new())).build_transform();
+    IesProfile::parse(&contents, transform)
+  }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LuminaireSpace;
+
+impl Space<3> for LuminaireSpace {}
+
+/// A goniometric table of luminous intensities (in candela) read from an IES LM-63 file. Vertical angles are measured
+/// from straight down (-y, before the profile's transform is applied) and horizontal angles counterclockwise about it
+/// from +x towards +z.
+#[derive(Debug)]
+pub struct IesProfile {
+  vertical_angles: Vec<Real>,
+  horizontal_angles: Vec<Real>,
+
+  /// The intensities of each vertical angle for each horizontal angle in turn
+  candela: Vec<Vec<Real>>,
+  transform: LocalToWorld<LuminaireSpace>
+}
+
+/// Finds where `x` falls in an increasing sequence, as an index and the fraction of the way to the next element.
+fn locate(angles: &[Real], x: Real) -> (usize, Real) {
+  if angles.len() < 2 || x <= angles[0] {
+    return (0, 0.0);
+  }
+
+  let i = angles.partition_point(|a| *a <= x).min(angles.len() - 1) - 1;
+  let width = angles[i + 1] - angles[i];
+  (i, if width > 0.0 { ((x - angles[i]) / width).clamp(0.0, 1.0) } else { 0.0 })
+}
+
+impl IesProfile {
+  fn parse(contents: &str, transform: LocalToWorld<LuminaireSpace>) -> IesProfile {
+    // Skip the keyword lines of the header, which end with the TILT line
+    let mut lines = contents.lines();
+    let tilt = lines
+      .by_ref()
+      .find_map(|line| line.trim().strip_prefix("TILT="))
+      .expect("IES profile has no TILT line!")
+      .trim()
+      .to_string();
+
+    let mut numbers = lines
+      .flat_map(|line| line.split(|c: char| c.is_whitespace() || c == ',').collect::<Vec<_>>())
+      .filter(|s| !s.is_empty())
+      .map(|s| s.parse::<Real>().expect("IES profile contains something other than numbers!"));
+    let mut next = || numbers.next().expect("IES profile ended early!");
+
+    // Lamp tilt data only matters for luminaires which are themselves tilted, so it is read and ignored
+    match tilt.as_str() {
+      "NONE" => {},
+      "INCLUDE" => {
+        next();
+        let num_tilt_angles = next() as usize;
+        (0..2 * num_tilt_angles).for_each(|_| {
+          next();
+        });
+      },
+      _ => panic!("IES profiles with tilt data in a separate file are not supported!")
+    }
+
+    let (_num_lamps, _lumens_per_lamp, candela_multiplier) = (next(), next(), next());
+    let (num_vertical_angles, num_horizontal_angles) = (next() as usize, next() as usize);
+    let photometric_type = next() as usize;
+    if photometric_type != 1 {
+      panic!("Only type C IES profiles are supported!");
+    }
+
+    let (_units, _width, _length, _height) = (next(), next(), next(), next());
+    let scale = candela_multiplier * next() * next();
+    let _input_watts = next();
+
+    let vertical_angles: Vec<_> = (0..num_vertical_angles).map(|_| next()).collect();
+    let horizontal_angles: Vec<_> = (0..num_horizontal_angles).map(|_| next()).collect();
+    let candela =
+      (0..num_horizontal_angles).map(|_| (0..num_vertical_angles).map(|_| next() * scale).collect()).collect();
+
+    IesProfile { vertical_angles, horizontal_angles, candela, transform }
+  }
+
+  /// The intensity emitted in direction `dir`, interpolated bilinearly from the table.
+  pub fn intensity(&self, dir: &WorldUnitVector) -> Real {
+    let d = self.transform.inverse_direction(dir).into_vector();
+    let theta = (-d[1]).clamp(-1.0, 1.0).acos().to_degrees();
+    let mut phi = d[2].atan2(d[0]).to_degrees().rem_euclid(360.0);
+
+    // Profiles only cover as much of the horizontal range as their symmetry requires. Those running from 90 to 270
+    // degrees are symmetric about the plane through 90 and 270 degrees
+    let first = *self.horizontal_angles.first().unwrap_or(&0.0);
+    let last = *self.horizontal_angles.last().unwrap_or(&0.0);
+    if first == 90.0 {
+      phi = if (90.0..=270.0).contains(&phi) { phi } else { (180.0 - phi).rem_euclid(360.0) };
+    } else if last <= 0.0 {
+      phi = 0.0;
+    } else if last <= 90.0 {
+      phi = if phi > 180.0 { 360.0 - phi } else { phi };
+      phi = if phi > 90.0 { 180.0 - phi } else { phi };
+    } else if last <= 180.0 && phi > 180.0 {
+      phi = 360.0 - phi;
+    }
+
+    let (i, s) = locate(&self.horizontal_angles, phi);
+    let (j, t) = locate(&self.vertical_angles, theta);
+    let value = |i: usize, j: usize| {
+      let row = &self.candela[i.min(self.candela.len() - 1)];
+      row[j.min(row.len() - 1)]
+    };
+
+    let near = value(i, j) * (1.0 - t) + value(i, j + 1) * t;
+    let far = value(i + 1, j) * (1.0 - t) + value(i + 1, j + 1) * t;
+    (near * (1.0 - s) + far * s).max(0.0)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// A type C profile covering horizontal angles from 90 to 270 degrees, whose intensity grows with the horizontal
+  /// angle and falls off towards the horizon.
+  const PROFILE: &str = "IESNA:LM-63-2002
+[TEST] Half of a luminaire symmetric about the 90-270 degree plane
+TILT=NONE
+1 1000 1 3 3 1 1 0 0 0
+1 1 100
+0 45 90
+90 180 270
+100 80 0
+200 160 0
+300 240 0
+";
+
+  fn intensity(profile: &IesProfile, theta: Real, phi: Real) -> Real {
+    let (theta, phi) = (theta.to_radians(), phi.to_radians());
+    let dir = WorldVector::from_array([theta.sin() * phi.cos(), -theta.cos(), theta.sin() * phi.sin()]).normalize();
+    profile.intensity(&dir)
+  }
+
+  fn profile() -> IesProfile { IesProfile::parse(PROFILE, TransformParameters::Composed(Vec::new()).build_transform()) }
+
+  #[test]
+  fn interpolates_within_the_table() {
+    let profile = profile();
+    assert!((intensity(&profile, 0.0, 180.0) - 200.0).abs() < 1e-3);
+    assert!((intensity(&profile, 22.5, 135.0) - 135.0).abs() < 1e-3);
+    assert!((intensity(&profile, 45.0, 225.0) - 200.0).abs() < 1e-3);
+  }
+
+  #[test]
+  fn folds_horizontal_angles_outside_of_the_table() {
+    let profile = profile();
+    for (theta, phi) in [(22.5, 135.0), (45.0, 180.0), (10.0, 240.0), (60.0, 260.0)] {
+      let folded = intensity(&profile, theta, 180.0 - phi);
+      assert!((folded - intensity(&profile, theta, phi)).abs() < 1e-3, "{theta} {phi}: {folded}");
+    }
+  }
+}
@@ -1,7 +1,10 @@
 use std::{fmt::Debug, sync::Arc};
 
 use crate::{
-  math::WorldUnitVector, raytracing::WorldSurfacePoint, sampling::ContinuousRandomVariable, spectrum::Spectrum
+  math::{PositiveReal, WorldUnitVector},
+  raytracing::WorldSurfacePoint,
+  sampling::ContinuousRandomVariable,
+  spectrum::Spectrum
 };
 
 #[typetag::deserialize(tag = "type")]
@@ -16,4 +19,8 @@ pub trait Light: Debug {
 
   fn random_emit_direction(&self)
     -> &dyn ContinuousRandomVariable<Param = WorldSurfacePoint, Sample = WorldUnitVector>;
+
+  /// A copy of this light adapted to being attached to a surface with the given total area, or `None` if the light
+  /// doesn't depend on it (lights specified by their total power need the area to work out their radiance).
+  fn with_surface_area(&self, _area: PositiveReal) -> Option<Arc<dyn Light>> { None }
 }
@@ -0,0 +1,41 @@
+use std::fmt::Debug;
+
+use crate::{
+  math::{PositiveReal, WorldPoint, WorldUnitVector},
+  sampling::Sampler,
+  spectrum::Spectrum
+};
+
+#[typetag::deserialize(tag = "type")]
+pub trait LightSourceParameters: Debug {
+  /// The name by which surfaces can include or exclude this light source, if any.
+  fn name(&self) -> Option<String>;
+
+  fn build_light_source(&self) -> Box<dyn LightSource>;
+}
+
+/// Light arriving at a point from a light source, sampled for direct lighting. For delta light sources, `radiance` is
+/// the irradiance the light contributes perpendicular to `dir` and `pdf` is one; otherwise `pdf` is a solid-angle
+/// density.
+#[derive(Debug, Clone)]
+pub struct LightSourceSample {
+  /// The direction from the receiving point towards the light
+  pub dir: WorldUnitVector,
+
+  /// The distance to the light, or `None` if the light is infinitely far away
+  pub dist: Option<PositiveReal>,
+  pub radiance: Spectrum,
+  pub pdf: PositiveReal,
+  pub is_delta: bool
+}
+
+/// A light which exists in the scene on its own rather than being attached to a surface.
+pub trait LightSource: Debug {
+  fn sample_incident(&self, point: &WorldPoint, sampler: &mut dyn Sampler) -> Option<LightSourceSample>;
+
+  /// The solid-angle density with which `sample_incident` produces `dir` at `point`, which is `None` for delta lights.
+  fn incident_pdf(&self, _point: &WorldPoint, _dir: &WorldUnitVector) -> Option<PositiveReal> { None }
+
+  /// The radiance arriving along a ray which travels in direction `dir` without hitting anything.
+  fn radiance_escaped(&self, _dir: &WorldUnitVector) -> Spectrum { Spectrum::none() }
+}
@@ -1,6 +1,16 @@
 mod diffuse_light;
+mod directional_light;
+mod environment_map;
+mod ies_profile;
 mod light;
+mod light_source;
 mod null_light;
+mod point_light;
+mod sky;
+mod spot_light;
+mod sun;
 
+pub use environment_map::dir_to_equirect_uv;
 pub use light::*;
+pub use light_source::*;
 pub use null_light::*;
@@ -0,0 +1,54 @@
+use serde::Deserialize;
+
+use super::{ies_profile::*, *};
+use crate::{math::*, sampling::Sampler, spectrum::*};
+
+#[derive(Debug, Deserialize)]
+struct PointLightParameters {
+  name: Option<String>,
+
+  position: [Real; 3],
+  intensity: ColorParameters,
+
+  /// An IES profile whose candela values scale the intensity in each direction
+  profile: Option<IesProfileParameters>
+}
+
+#[typetag::deserialize(name = "point")]
+impl LightSourceParameters for PointLightParameters {
+  fn name(&self) -> Option<String> { self.name.clone() }
+
+  fn build_light_source(&self) -> Box<dyn LightSource> {
+    Box::new(PointLight {
+      position: Point::from_array(self.position),
+      intensity: self.intensity.build_color(),
+      profile: self.profile.as_ref().map(IesProfileParameters::build_profile)
+    })
+  }
+}
+
+/// Emits the same radiant intensity in every direction from a single point, unless shaped by an IES profile.
+#[derive(Debug)]
+pub struct PointLight {
+  position: WorldPoint,
+  intensity: Spectrum,
+  profile: Option<IesProfile>
+}
+
+impl LightSource for PointLight {
+  fn sample_incident(&self, point: &WorldPoint, _: &mut dyn Sampler) -> Option<LightSourceSample> {
+    let (dir, dist) = (self.position - *point).normalize_with_norm();
+    let scale = self.profile.as_ref().map_or(1.0, |profile| profile.intensity(&-dir));
+    if scale <= 0.0 {
+      return None;
+    }
+
+    Some(LightSourceSample {
+      dir,
+      dist: Some(PositiveReal::new(dist)?),
+      radiance: self.intensity * (scale / (dist * dist)),
+      pdf: PositiveReal::ONE,
+      is_delta: true
+    })
+  }
+}
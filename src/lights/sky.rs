@@ -0,0 +1,216 @@
+use image::{ImageBuffer, Rgb};
+use serde::Deserialize;
+
+use super::{environment_map::*, sun::*, *};
+use crate::{math::*, sampling::Sampler, spectrum::*};
+
+/// The resolution of the equirectangular image the sky is baked into before rendering
+const SKY_RESOLUTION: (u32, u32) = (512, 256);
+
+fn default_turbidity() -> Real { 3.0 }
+
+fn default_ground_albedo() -> ColorParameters { ColorParameters::Single(0.3) }
+
+fn default_intensity() -> Real { 1.0 }
+
+#[derive(Debug, Deserialize)]
+struct SkyParameters {
+  name: Option<String>,
+
+  /// The direction from the ground towards the sun
+  #[serde(alias = "sun-direction")]
+  sun_direction: [Real; 3],
+
+  #[serde(default = "default_turbidity")]
+  turbidity: Real,
+
+  #[serde(alias = "ground-albedo", default = "default_ground_albedo")]
+  ground_albedo: ColorParameters,
+
+  #[serde(default = "default_intensity")]
+  intensity: Real,
+
+  /// A sun disk to add to the sky, placed in the direction of the sun
+  sun: Option<SunDiskParameters>
+}
+
+#[typetag::deserialize(name = "sky")]
+impl LightSourceParameters for SkyParameters {
+  fn name(&self) -> Option<String> { self.name.clone() }
+
+  fn build_light_source(&self) -> Box<dyn LightSource> {
+    let to_sun: WorldUnitVector = UnitVector::from_array(self.sun_direction);
+    let sun = self.sun.as_ref().map(|s| s.build_sun(to_sun));
+    let model = PreethamSky::new(to_sun.into_vector()[1], self.turbidity);
+
+    // Bake the sky into an image, keeping track of the irradiance it delivers to the ground
+    let (width, height) = SKY_RESOLUTION;
+    let sun_dir: UnitVector3<EnvironmentSpace> = UnitVector::from_array(self.sun_direction);
+    let mut image = ImageBuffer::<Rgb<f32>, Vec<f32>>::new(width, height);
+    let mut ground_irradiance = Spectrum::none();
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+      let (u, v) = ((x as Real + 0.5) / width as Real, (y as Real + 0.5) / height as Real);
+      let (dir, sin_theta) = equirectangular_direction(u, v);
+      let cos_theta = dir.into_vector()[1];
+      if cos_theta > 0.0 {
+        let radiance = model.radiance(cos_theta, dir.dot(&sun_dir).clamp(-1.0, 1.0).acos()) * self.intensity;
+        let solid_angle = 2.0 * PI * PI * sin_theta / (width * height) as Real;
+        ground_irradiance += radiance * (cos_theta * solid_angle);
+        *pixel = Rgb([radiance.r(), radiance.g(), radiance.b()]);
+      }
+    }
+
+    // The ground below the horizon is a diffuse reflector lit by the sky (and the sun, if there is one)
+    if let Some(sun) = &sun {
+      ground_irradiance += sun.irradiance() * sun.direction().into_vector()[1].max(0.0);
+    }
+
+    let ground = self.ground_albedo.build_color() * ground_irradiance * INV_PI;
+    for (_, y, pixel) in image.enumerate_pixels_mut() {
+      if y >= height / 2 {
+        *pixel = Rgb([ground.r(), ground.g(), ground.b()]);
+      }
+    }
+
+    let transform = TransformParameters::Composed(Vec::new()).build_transform();
+    Box::new(Sky { sky: EnvironmentMap::new(image, 1.0, transform), sun })
+  }
+}
+
+/// The Perez distribution coefficients and zenith value of one of the Y, x and y channels of the Preetham sky.
+struct PerezChannel {
+  coefficients: [Real; 5],
+  zenith: Real
+}
+
+impl PerezChannel {
+  fn perez(&self, cos_theta: Real, gamma: Real) -> Real {
+    let [a, b, c, d, e] = self.coefficients;
+    (1.0 + a * (b / cos_theta.max(0.01)).exp()) * (1.0 + c * (d * gamma).exp() + e * gamma.cos() * gamma.cos())
+  }
+}
+
+/// The analytic daylight model of Preetham et al. (1999), giving sky radiance from the angle to the zenith and the
+/// angle to the sun, with luminance in kcd/m^2.
+struct PreethamSky {
+  channels: [PerezChannel; 3],
+  sun_theta: Real
+}
+
+impl PreethamSky {
+  fn new(sun_height: Real, turbidity: Real) -> Self {
+    let t = turbidity.max(1.0);
+    let sun_theta = sun_height.clamp(0.0, 1.0).acos();
+
+    // Zenith luminance and chromaticity fitted as functions of turbidity and the sun's zenith angle
+    let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * sun_theta);
+    let zenith_luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+    let chromaticity = |m: [[Real; 4]; 3]| {
+      let thetas = [sun_theta.powi(3), sun_theta.powi(2), sun_theta, 1.0];
+      let ts = [t * t, t, 1.0];
+      (0..3).map(|i| ts[i] * (0..4).map(|j| m[i][j] * thetas[j]).sum::<Real>()).sum::<Real>()
+    };
+
+    let zenith_x = chromaticity([
+      [0.00166, -0.00375, 0.00209, 0.0],
+      [-0.02903, 0.06377, -0.03202, 0.00394],
+      [0.11693, -0.21196, 0.06052, 0.25886]
+    ]);
+    let zenith_y = chromaticity([
+      [0.00275, -0.00610, 0.00317, 0.0],
+      [-0.04214, 0.08970, -0.04153, 0.00516],
+      [0.15346, -0.26756, 0.06670, 0.26688]
+    ]);
+
+    let channels = [
+      PerezChannel {
+        coefficients: [
+          0.1787 * t - 1.4630,
+          -0.3554 * t + 0.4275,
+          -0.0227 * t + 5.3251,
+          0.1206 * t - 2.5771,
+          -0.0670 * t + 0.3703
+        ],
+        zenith: zenith_luminance
+      },
+      PerezChannel {
+        coefficients: [
+          -0.0193 * t - 0.2592,
+          -0.0665 * t + 0.0008,
+          -0.0004 * t + 0.2125,
+          -0.0641 * t - 0.8989,
+          -0.0033 * t + 0.0452
+        ],
+        zenith: zenith_x
+      },
+      PerezChannel {
+        coefficients: [
+          -0.0167 * t - 0.2608,
+          -0.0950 * t + 0.0092,
+          -0.0079 * t + 0.2102,
+          -0.0441 * t - 1.6537,
+          -0.0109 * t + 0.0529
+        ],
+        zenith: zenith_y
+      }
+    ];
+
+    Self { channels, sun_theta }
+  }
+
+  /// The radiance of the sky in a direction above the horizon, given the cosine of its angle to the zenith and its
+  /// angle to the sun.
+  fn radiance(&self, cos_theta: Real, gamma: Real) -> Spectrum {
+    let [luminance, x, y] =
+      self.channels.each_ref().map(|c| c.zenith * c.perez(cos_theta, gamma) / c.perez(1.0, self.sun_theta));
+
+    // Convert from xyY to XYZ and then to the working color space
+    let (big_x, big_y, big_z) = (x * luminance / y, luminance, (1.0 - x - y) * luminance / y);
+    xyz_to_spectrum([big_x as f64, big_y as f64, big_z as f64])
+  }
+}
+
+/// A Preetham sky baked into an environment map, along with an optional sun disk. Directions are sampled from the sky
+/// or the sun with equal probability when there is a sun.
+#[derive(Debug)]
+pub struct Sky {
+  sky: EnvironmentMap,
+  sun: Option<SunDisk>
+}
+
+impl Sky {
+  fn mixture_pdf(&self, point: &WorldPoint, dir: &WorldUnitVector) -> Option<PositiveReal> {
+    let sky_pdf = self.sky.incident_pdf(point, dir).map_or(0.0, PositiveReal::into_inner);
+    match &self.sun {
+      Some(sun) => {
+        let sun_pdf = sun.incident_pdf(point, dir).map_or(0.0, PositiveReal::into_inner);
+        PositiveReal::new((sky_pdf + sun_pdf) / 2.0)
+      },
+      None => PositiveReal::new(sky_pdf)
+    }
+  }
+}
+
+impl LightSource for Sky {
+  fn sample_incident(&self, point: &WorldPoint, sampler: &mut dyn Sampler) -> Option<LightSourceSample> {
+    let sample = match &self.sun {
+      Some(sun) if sampler.next().into_inner() < 0.5 => sun.sample_incident(point, sampler),
+      _ => self.sky.sample_incident(point, sampler)
+    }?;
+
+    Some(LightSourceSample {
+      radiance: self.radiance_escaped(&sample.dir),
+      pdf: self.mixture_pdf(point, &sample.dir)?,
+      ..sample
+    })
+  }
+
+  fn incident_pdf(&self, point: &WorldPoint, dir: &WorldUnitVector) -> Option<PositiveReal> {
+    self.mixture_pdf(point, dir)
+  }
+
+  fn radiance_escaped(&self, dir: &WorldUnitVector) -> Spectrum {
+    let sun_radiance = self.sun.as_ref().map_or(Spectrum::none(), |sun| sun.radiance_escaped(dir));
+    self.sky.radiance_escaped(dir) + sun_radiance
+  }
+}
@@ -0,0 +1,84 @@
+use serde::Deserialize;
+
+use super::{ies_profile::*, *};
+use crate::{math::*, sampling::Sampler, spectrum::*};
+
+#[derive(Debug, Deserialize)]
+struct SpotLightParameters {
+  name: Option<String>,
+
+  position: [Real; 3],
+  direction: [Real; 3],
+  intensity: ColorParameters,
+
+  /// The angle in degrees between the axis of the spot and the edge of its cone
+  #[serde(alias = "cone-angle")]
+  cone_angle: Real,
+
+  /// The angle in degrees from the axis at which the intensity starts falling off, which defaults to the cone angle
+  #[serde(alias = "falloff-start")]
+  falloff_start: Option<Real>,
+
+  /// An IES profile whose candela values scale the intensity in each direction within the cone
+  profile: Option<IesProfileParameters>
+}
+
+#[typetag::deserialize(name = "spot")]
+impl LightSourceParameters for SpotLightParameters {
+  fn name(&self) -> Option<String> { self.name.clone() }
+
+  fn build_light_source(&self) -> Box<dyn LightSource> {
+    let cone_angle = self.cone_angle.clamp(0.0, 180.0);
+    let falloff_start = self.falloff_start.unwrap_or(cone_angle).clamp(0.0, cone_angle);
+    Box::new(SpotLight {
+      position: Point::from_array(self.position),
+      axis: UnitVector::from_array(self.direction),
+      intensity: self.intensity.build_color(),
+      cos_cone_angle: cone_angle.to_radians().cos(),
+      cos_falloff_start: falloff_start.to_radians().cos(),
+      profile: self.profile.as_ref().map(IesProfileParameters::build_profile)
+    })
+  }
+}
+
+/// A point light which only shines within a cone about its axis, fading out smoothly towards the edge of the cone.
+#[derive(Debug)]
+pub struct SpotLight {
+  position: WorldPoint,
+  axis: WorldUnitVector,
+  intensity: Spectrum,
+  cos_cone_angle: Real,
+  cos_falloff_start: Real,
+  profile: Option<IesProfile>
+}
+
+impl SpotLight {
+  fn falloff(&self, cos_theta: Real) -> Real {
+    if cos_theta >= self.cos_falloff_start {
+      1.0
+    } else if cos_theta <= self.cos_cone_angle {
+      0.0
+    } else {
+      let t = (cos_theta - self.cos_cone_angle) / (self.cos_falloff_start - self.cos_cone_angle);
+      t * t * (3.0 - 2.0 * t)
+    }
+  }
+}
+
+impl LightSource for SpotLight {
+  fn sample_incident(&self, point: &WorldPoint, _: &mut dyn Sampler) -> Option<LightSourceSample> {
+    let (dir, dist) = (self.position - *point).normalize_with_norm();
+    let falloff = self.falloff(-dir.dot(&self.axis)) * self.profile.as_ref().map_or(1.0, |p| p.intensity(&-dir));
+    if falloff <= 0.0 {
+      return None;
+    }
+
+    Some(LightSourceSample {
+      dir,
+      dist: Some(PositiveReal::new(dist)?),
+      radiance: self.intensity * (falloff / (dist * dist)),
+      pdf: PositiveReal::ONE,
+      is_delta: true
+    })
+  }
+}
@@ -0,0 +1,93 @@
+use serde::Deserialize;
+
+use super::*;
+use crate::{math::*, sampling::Sampler, spectrum::*};
+
+fn default_angular_radius() -> Real { 0.2665 }
+
+/// The parameters of a sun disk apart from its direction, which may be given by a sky instead.
+#[derive(Debug, Deserialize)]
+pub(super) struct SunDiskParameters {
+  /// The irradiance the sun delivers to a surface facing it
+  irradiance: ColorParameters,
+
+  /// The angle in degrees between the center and edge of the disk as seen from the ground
+  #[serde(alias = "angular-radius", default = "default_angular_radius")]
+  angular_radius: Real
+}
+
+impl SunDiskParameters {
+  pub(super) fn build_sun(&self, to_sun: WorldUnitVector) -> SunDisk {
+    let cos_angular_radius = self.angular_radius.clamp(1e-3, 90.0).to_radians().cos();
+    let solid_angle = 2.0 * PI * (1.0 - cos_angular_radius);
+    SunDisk {
+      to_sun,
+      cos_angular_radius,
+      radiance: self.irradiance.build_color() / solid_angle,
+      pdf: PositiveReal::new_unchecked(1.0 / solid_angle)
+    }
+  }
+}
+
+#[derive(Debug, Deserialize)]
+struct SunParameters {
+  name: Option<String>,
+
+  /// The direction from the ground towards the sun
+  direction: [Real; 3],
+
+  #[serde(flatten)]
+  disk: SunDiskParameters
+}
+
+#[typetag::deserialize(name = "sun")]
+impl LightSourceParameters for SunParameters {
+  fn name(&self) -> Option<String> { self.name.clone() }
+
+  fn build_light_source(&self) -> Box<dyn LightSource> {
+    Box::new(self.disk.build_sun(UnitVector::from_array(self.direction)))
+  }
+}
+
+/// A distant disk of uniform radiance, which unlike a directional light casts soft shadows and can be seen in
+/// reflections.
+#[derive(Debug)]
+pub struct SunDisk {
+  to_sun: WorldUnitVector,
+  cos_angular_radius: Real,
+  radiance: Spectrum,
+  pdf: PositiveReal
+}
+
+impl SunDisk {
+  pub(super) fn direction(&self) -> WorldUnitVector { self.to_sun }
+
+  /// The irradiance the sun delivers to a surface facing it.
+  pub(super) fn irradiance(&self) -> Spectrum { self.radiance / self.pdf.into_inner() }
+}
+
+impl LightSource for SunDisk {
+  fn sample_incident(&self, _: &WorldPoint, sampler: &mut dyn Sampler) -> Option<LightSourceSample> {
+    // Sample the cone of directions subtended by the disk uniformly
+    let cos_theta = sampler.random_in_closed(self.cos_angular_radius, 1.0);
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = sampler.random_in_closed_open(0.0, 2.0 * PI);
+    let (tangent, bitangent) = self.to_sun.orthonormal_basis();
+    let dir =
+      (tangent * (sin_theta * phi.cos()) + bitangent * (sin_theta * phi.sin()) + self.to_sun * cos_theta).normalize();
+
+    Some(LightSourceSample { dir, dist: None, radiance: self.radiance, pdf: self.pdf, is_delta: false })
+  }
+
+  fn incident_pdf(&self, _: &WorldPoint, dir: &WorldUnitVector) -> Option<PositiveReal> {
+    (dir.dot(&self.to_sun) >= self.cos_angular_radius).then_some(self.pdf)
+  }
+
+  fn radiance_escaped(&self, dir: &WorldUnitVector) -> Spectrum {
+    if dir.dot(&self.to_sun) >= self.cos_angular_radius {
+      self.radiance
+    } else {
+      Spectrum::none()
+    }
+  }
+}
@@ -2,7 +2,9 @@ use std::{error::Error, fs::File, io::BufReader, time::Duration};
 
 use clap::Parser;
 use renderer::Renderer;
+use surfaces::LightSelection;
 
+mod background;
 mod camera;
 mod integrators;
 mod lights;
@@ -17,7 +19,6 @@ mod surfaces;
 mod textures;
 
 // Top Priority:
-// TODO: Deal with BSDFs which aren't self-adjoint (i.e. dielectrics and anything using shading normals)
 // TODO: Add a debug mode which checks for NaNs and infinites and stuff like that?
 // TODO: Does glass need PMF?
 // TODO: Properly handle shading normals in BSDF sampling
@@ -25,7 +26,6 @@ mod textures;
 // Major Features:
 // TODO: Allow rays to carry more information
 // TODO: Stratified sampling
-// TODO: Direct-lighting MIS and mixture sampling
 // TODO: Interfering mediums
 // TODO: Fourier materials
 // TODO: Fancier integrators
@@ -35,13 +35,10 @@ mod textures;
 // TODO: GPU acceleration
 
 // Minor Features:
-// TODO: Perlin noise texture
 // TODO: All the PA2 materials
-// TODO: Environment map
 
 // Minor Code Improvements:
 // TODO: Move camera into scene, make samples-per-pixel an integrator-specific thing
-// TODO: Allow user to customize whether we use BVH or surface list for the emissive partition
 // TODO: All parameter structs should be consumed upon building their target
 // TODO: Put parsing in it's own place
 // TODO: Make a progress bar wrapper
@@ -83,7 +80,8 @@ pub struct RenderSettings {
 #[derive(Debug, Clone, Copy)]
 pub struct BuildSettings {
   num_threads: usize,
-  use_progress_bar: bool
+  use_progress_bar: bool,
+  light_selection: LightSelection
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
@@ -104,7 +102,9 @@ fn main() -> Result<(), Box<dyn Error>> {
   let build_time = std::time::Instant::now();
   let reader = BufReader::new(File::open(scene_file)?);
   let params = serde_json::from_reader(reader)?;
-  let renderer = Renderer::build(params, BuildSettings { num_threads, use_progress_bar: !no_progress_bar })?;
+  let settings =
+    BuildSettings { num_threads, use_progress_bar: !no_progress_bar, light_selection: LightSelection::default() };
+  let renderer = Renderer::build(params, settings)?;
 
   println!("Building complete! Time: {}\n", duration_to_hms(&build_time.elapsed()));
 
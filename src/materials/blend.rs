@@ -0,0 +1,134 @@
+use std::{collections::HashMap, sync::Arc};
+
+use serde::Deserialize;
+
+use super::*;
+use crate::{
+  math::{PositiveReal, Real, WorldUnitVector},
+  raytracing::*,
+  sampling::*,
+  spectrum::Spectrum,
+  textures::*
+};
+
+#[derive(Debug, Deserialize)]
+struct BlendParameters {
+  name: String,
+  first: String,
+  second: String,
+  weight: Box<dyn TextureParameters>
+}
+
+#[typetag::deserialize(name = "blend")]
+impl MaterialParameters for BlendParameters {
+  fn name(&self) -> String { self.name.clone() }
+
+  fn build_material(&self, materials: &HashMap<String, Arc<dyn Material>>) -> Arc<dyn Material> {
+    let lookup = |name: &String| {
+      materials.get(name).unwrap_or_else(|| panic!("Material \"{name}\" must be declared before \"{}\"!", self.name))
+    };
+
+    Arc::new(Blend {
+      first: lookup(&self.first).clone(),
+      second: lookup(&self.second).clone(),
+      weight: self.weight.build_texture()
+    })
+  }
+}
+
+/// A stochastic mixture of two materials, where the weight texture gives the fraction of the second material at each
+/// point (so a weight of zero is entirely the first material).
+#[derive(Debug)]
+pub struct Blend {
+  first: Arc<dyn Material>,
+  second: Arc<dyn Material>,
+  weight: Arc<dyn Texture>
+}
+
+impl Blend {
+  fn weight(&self, hit: &WorldSurfacePoint) -> Real {
+    self.weight.value(&hit.texture_point()).luminance().clamp(0.0, 1.0)
+  }
+
+  /// Chooses one of the materials and samples it with `sample`.
+  fn sample_chosen(
+    &self,
+    hit: &WorldSurfacePoint,
+    out_dir: &WorldUnitVector,
+    mode: TransportMode,
+    sampler: &mut dyn Sampler,
+    sample: impl FnOnce(&dyn Material, &mut dyn Sampler) -> Option<BsdfSample>
+  ) -> Option<BsdfSample> {
+    let w = self.weight(hit);
+    let (chosen, chosen_probability) =
+      if sampler.next().into_inner() < w { (&self.second, w) } else { (&self.first, 1.0 - w) };
+
+    let sample = sample(chosen.as_ref(), sampler)?;
+    if sample.is_delta {
+      // Only the chosen material can have produced this direction, so its weight is scaled down by the blend weight
+      // exactly as much as its probability is.
+      Some(BsdfSample {
+        bsdf_cos: sample.bsdf_cos * chosen_probability,
+        pdf: PositiveReal::new(sample.pdf.into_inner() * chosen_probability)?,
+        ..sample
+      })
+    } else {
+      // Either material's continuous lobes could have produced this direction, so evaluate the full mixture.
+      Some(BsdfSample {
+        bsdf_cos: self.bsdf_cos(hit, &sample.in_dir, out_dir, mode),
+        pdf: self.bsdf_pdf(hit, &sample.in_dir, out_dir)?,
+        ..sample
+      })
+    }
+  }
+}
+
+impl Material for Blend {
+  fn bsdf_cos(
+    &self,
+    hit: &WorldSurfacePoint,
+    in_dir: &WorldUnitVector,
+    out_dir: &WorldUnitVector,
+    mode: TransportMode
+  ) -> Spectrum {
+    let w = self.weight(hit);
+    self.first.bsdf_cos(hit, in_dir, out_dir, mode) * (1.0 - w) + self.second.bsdf_cos(hit, in_dir, out_dir, mode) * w
+  }
+
+  fn bsdf_pdf(
+    &self,
+    hit: &WorldSurfacePoint,
+    in_dir: &WorldUnitVector,
+    out_dir: &WorldUnitVector
+  ) -> Option<PositiveReal> {
+    let w = self.weight(hit);
+    let first_pdf = self.first.bsdf_pdf(hit, in_dir, out_dir).map_or(0.0, PositiveReal::into_inner);
+    let second_pdf = self.second.bsdf_pdf(hit, in_dir, out_dir).map_or(0.0, PositiveReal::into_inner);
+    PositiveReal::new(first_pdf * (1.0 - w) + second_pdf * w)
+  }
+
+  fn sample_bsdf(
+    &self,
+    hit: &WorldSurfacePoint,
+    out_dir: &WorldUnitVector,
+    mode: TransportMode,
+    sampler: &mut dyn Sampler
+  ) -> Option<BsdfSample> {
+    self.sample_chosen(hit, out_dir, mode, sampler, |chosen, sampler| chosen.sample_bsdf(hit, out_dir, mode, sampler))
+  }
+
+  fn is_dispersive(&self) -> bool { self.first.is_dispersive() || self.second.is_dispersive() }
+
+  fn sample_bsdf_at_wavelength(
+    &self,
+    hit: &WorldSurfacePoint,
+    out_dir: &WorldUnitVector,
+    mode: TransportMode,
+    wavelength: Real,
+    sampler: &mut dyn Sampler
+  ) -> Option<BsdfSample> {
+    self.sample_chosen(hit, out_dir, mode, sampler, |chosen, sampler| {
+      chosen.sample_bsdf_at_wavelength(hit, out_dir, mode, wavelength, sampler)
+    })
+  }
+}
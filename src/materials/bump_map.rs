@@ -0,0 +1,132 @@
+use std::{collections::HashMap, sync::Arc};
+
+use serde::Deserialize;
+
+use super::*;
+use crate::{
+  math::{PositiveReal, Real, VectorLike, WorldUnitVector},
+  raytracing::*,
+  sampling::*,
+  spectrum::Spectrum,
+  textures::*
+};
+
+fn default_scale() -> Real { 1.0 }
+
+#[derive(Debug, Deserialize)]
+struct BumpMapParameters {
+  name: String,
+  material: String,
+  height: Box<dyn TextureParameters>,
+
+  #[serde(default = "default_scale")]
+  scale: Real
+}
+
+#[typetag::deserialize(name = "bump-map")]
+impl MaterialParameters for BumpMapParameters {
+  fn name(&self) -> String { self.name.clone() }
+
+  fn build_material(&self, materials: &HashMap<String, Arc<dyn Material>>) -> Arc<dyn Material> {
+    Arc::new(BumpMap {
+      material: materials
+        .get(&self.material)
+        .unwrap_or_else(|| panic!("Material \"{}\" must be declared before \"{}\"!", self.material, self.name))
+        .clone(),
+      height: self.height.build_texture(),
+      scale: self.scale
+    })
+  }
+}
+
+/// The step in texture space used to estimate the derivatives of the height texture with finite differences
+const BUMP_DELTA: Real = 0.0005;
+
+/// Replaces the shading normal of another material with that of the surface displaced along its shading normal by a
+/// scalar height texture (taken as the luminance of the texture, multiplied by `scale`).
+#[derive(Debug)]
+pub struct BumpMap {
+  material: Arc<dyn Material>,
+  height: Arc<dyn Texture>,
+  scale: Real
+}
+
+impl BumpMap {
+  fn height(&self, point: &TexturePoint) -> Real { self.height.value(point).luminance() * self.scale }
+
+  fn perturb(&self, hit: &WorldSurfacePoint) -> WorldSurfacePoint {
+    // Step along u and v in object space too, for solid height textures
+    let point = hit.texture_point();
+    let offset = |du: Real, dv: Real| TexturePoint {
+      tex_coord: TextureCoordinate::from_array([hit.tex_coord[0] + du, hit.tex_coord[1] + dv]),
+      object_point: hit.object_point + hit.object_dpdu * du + hit.object_dpdv * dv,
+      ..point
+    };
+
+    let height = self.height(&point);
+    let dhdu = (self.height(&offset(BUMP_DELTA, 0.0)) - height) / BUMP_DELTA;
+    let dhdv = (self.height(&offset(0.0, BUMP_DELTA)) - height) / BUMP_DELTA;
+
+    // Differentiate p + h(u, v) * n, neglecting the change in the normal itself since it is usually tiny
+    let n = hit.shading_normal;
+    let dpdu = hit.dpdu + n * dhdu;
+    let dpdv = hit.dpdv + n * dhdv;
+    let cross = dpdu.cross(&dpdv);
+    if cross.norm_squared() == 0.0 {
+      return hit.clone();
+    }
+
+    let mut bumped = cross.normalize();
+    if bumped.dot(&n) < 0.0 {
+      bumped = -bumped;
+    }
+
+    WorldSurfacePoint { shading_normal: bumped, ..hit.clone() }
+  }
+}
+
+impl Material for BumpMap {
+  fn bsdf_cos(
+    &self,
+    hit: &WorldSurfacePoint,
+    in_dir: &WorldUnitVector,
+    out_dir: &WorldUnitVector,
+    mode: TransportMode
+  ) -> Spectrum {
+    self.material.bsdf_cos(&self.perturb(hit), in_dir, out_dir, mode)
+  }
+
+  fn bsdf_pdf(
+    &self,
+    hit: &WorldSurfacePoint,
+    in_dir: &WorldUnitVector,
+    out_dir: &WorldUnitVector
+  ) -> Option<PositiveReal> {
+    self.material.bsdf_pdf(&self.perturb(hit), in_dir, out_dir)
+  }
+
+  fn sample_bsdf(
+    &self,
+    hit: &WorldSurfacePoint,
+    out_dir: &WorldUnitVector,
+    mode: TransportMode,
+    sampler: &mut dyn Sampler
+  ) -> Option<BsdfSample> {
+    self.material.sample_bsdf(&self.perturb(hit), out_dir, mode, sampler)
+  }
+
+  fn is_dispersive(&self) -> bool { self.material.is_dispersive() }
+
+  fn sample_bsdf_at_wavelength(
+    &self,
+    hit: &WorldSurfacePoint,
+    out_dir: &WorldUnitVector,
+    mode: TransportMode,
+    wavelength: Real,
+    sampler: &mut dyn Sampler
+  ) -> Option<BsdfSample> {
+    self.material.sample_bsdf_at_wavelength(&self.perturb(hit), out_dir, mode, wavelength, sampler)
+  }
+
+  fn interior(&self, hit: &WorldSurfacePoint) -> Option<Medium> { self.material.interior(hit) }
+}
@@ -1,4 +1,4 @@
-use std::{ops::Neg, sync::Arc};
+use std::{collections::HashMap, ops::Neg, sync::Arc};
 
 use serde::Deserialize;
 
@@ -9,20 +9,15 @@ use crate::{math::*, raytracing::*, sampling::*, spectrum::Spectrum, textures::*
 struct DieletricParameters {
   name: String,
   albedo: Box<dyn TextureParameters>,
-  ior: Real
+  ior: IorParameters
 }
 
 #[typetag::deserialize(name = "dielectric")]
 impl MaterialParameters for DieletricParameters {
   fn name(&self) -> String { self.name.clone() }
 
-  fn build_material(&self) -> Arc<dyn Material> {
-    Arc::new(Dieletric {
-      albedo: self.albedo.build_texture(),
-      scatter_random_var: ScatterRandomVariable::Specular(Box::new(RefractRandomVariable {
-        index_of_refraction: self.ior
-      }))
-    })
+  fn build_material(&self, _: &HashMap<String, Arc<dyn Material>>) -> Arc<dyn Material> {
+    Arc::new(Dieletric::new(self.albedo.build_texture(), self.ior.build_ior()))
   }
 }
 
@@ -32,14 +27,12 @@ struct RefractRandomVariable {
 }
 
 impl DiscreteRandomVariable for RefractRandomVariable {
-  type Param = (WorldSurfacePoint, WorldUnitVector);
-  type Sample = WorldUnitVector;
+  type Param = ScatterParameter;
+  /// The scattered direction, the probability of having chosen it, and (if the direction was refracted) the ratio of
+  /// the index of refraction on the side of `out_dir` to the index of refraction on the side of the sample
+  type Sample = (WorldUnitVector, PositiveReal, Option<Real>);
 
-  fn sample(
-    &self,
-    (hit, out_dir): &(WorldSurfacePoint, WorldUnitVector),
-    sampler: &mut dyn Sampler
-  ) -> Option<WorldUnitVector> {
+  fn sample(&self, (hit, out_dir): &Self::Param, sampler: &mut dyn Sampler) -> Option<Self::Sample> {
     // Ensure normal and IOR are correctly oriented (i.e. for whether ray is entering or exiting)
     let mut normal = hit.shading_normal;
     let mut eta_in = 1.0;
@@ -51,32 +44,38 @@ impl DiscreteRandomVariable for RefractRandomVariable {
 
     let cos_theta_in = out_dir.dot(&normal);
     let reflected = out_dir.reflect_about(normal);
-    let scattered_dir;
     if let Some((refracted, cos_theta_out)) = refract(out_dir, normal, eta_in / eta_out) {
       // Compute Fresnel coefficient (probability of reflection)
-      let eta_out_cos_in = eta_out * cos_theta_in;
-      let eta_in_cos_out = eta_in * cos_theta_out;
-      let rho_parallel = (eta_out_cos_in - eta_in_cos_out) / (eta_out_cos_in + eta_in_cos_out);
-      let eta_in_cos_in = eta_in * cos_theta_in;
-      let eta_out_cos_out = eta_out * cos_theta_out;
-      let rho_perp = (eta_in_cos_in - eta_out_cos_out) / (eta_in_cos_in + eta_out_cos_out);
-      let reflect_probability = (rho_parallel * rho_parallel + rho_perp * rho_perp) / 2.0;
+      let reflect_probability = fresnel_reflectance(cos_theta_in, cos_theta_out, eta_in, eta_out);
 
       // Refract or reflect based on the above probability
-      let should_reflect = sampler.next().into_inner() < reflect_probability;
-      scattered_dir = if should_reflect { reflected } else { refracted }
+      if sampler.next().into_inner() < reflect_probability {
+        PositiveReal::new(reflect_probability).map(|p| (reflected, p, None))
+      } else {
+        PositiveReal::new(1.0 - reflect_probability).map(|p| (refracted, p, Some(eta_in / eta_out)))
+      }
     } else {
-      scattered_dir = reflected;
+      Some((reflected, PositiveReal::ONE, None))
     }
-
-    Some(scattered_dir)
   }
 }
 
 #[derive(Debug)]
 pub struct Dieletric {
   albedo: Arc<dyn Texture>,
-  scatter_random_var: ScatterRandomVariable
+  ior: IndexOfRefraction
+}
+
+/// The fraction of unpolarized light reflected at a smooth dielectric interface, given the cosines of the angles the
+/// incident and refracted directions make with the normal and the indices of refraction on their respective sides.
+pub(super) fn fresnel_reflectance(cos_theta_in: Real, cos_theta_out: Real, eta_in: Real, eta_out: Real) -> Real {
+  let eta_out_cos_in = eta_out * cos_theta_in;
+  let eta_in_cos_out = eta_in * cos_theta_out;
+  let rho_parallel = (eta_out_cos_in - eta_in_cos_out) / (eta_out_cos_in + eta_in_cos_out);
+  let eta_in_cos_in = eta_in * cos_theta_in;
+  let eta_out_cos_out = eta_out * cos_theta_out;
+  let rho_perp = (eta_in_cos_in - eta_out_cos_out) / (eta_in_cos_in + eta_out_cos_out);
+  (rho_parallel * rho_parallel + rho_perp * rho_perp) / 2.0
 }
 
 fn refract<S: Space<3>>(
@@ -96,10 +95,63 @@ fn refract<S: Space<3>>(
   })
 }
 
+impl Dieletric {
+  pub(super) fn new(albedo: Arc<dyn Texture>, ior: IndexOfRefraction) -> Self { Self { albedo, ior } }
+
+  fn sample_with_ior(
+    &self,
+    hit: &WorldSurfacePoint,
+    out_dir: &WorldUnitVector,
+    mode: TransportMode,
+    index_of_refraction: Real,
+    sampler: &mut dyn Sampler
+  ) -> Option<BsdfSample> {
+    let refract_random_var = RefractRandomVariable { index_of_refraction };
+    let (in_dir, probability, maybe_eta_ratio) = refract_random_var.sample(&(hit.clone(), *out_dir), sampler)?;
+    let mut weight = probability.into_inner() * shading_normal_correction(hit, &in_dir, out_dir, mode);
+
+    // Radiance is compressed into a smaller solid angle when entering a denser medium (and expanded when leaving one),
+    // but importance is not; this is what makes refraction non-symmetric.
+    if let (Some(eta_ratio), TransportMode::Radiance) = (maybe_eta_ratio, mode) {
+      weight *= eta_ratio * eta_ratio;
+    }
+
+    Some(BsdfSample {
+      in_dir,
+      bsdf_cos: self.albedo.value(&hit.texture_point()) * weight,
+      pdf: probability,
+      is_delta: true
+    })
+  }
+}
+
 impl Material for Dieletric {
-  fn bsdf_cos(&self, hit: &WorldSurfacePoint, _: &WorldUnitVector, _: &WorldUnitVector) -> Spectrum {
-    self.albedo.value(&hit.tex_coord)
+  fn bsdf_cos(&self, _: &WorldSurfacePoint, _: &WorldUnitVector, _: &WorldUnitVector, _: TransportMode) -> Spectrum {
+    Spectrum::none()
+  }
+
+  fn bsdf_pdf(&self, _: &WorldSurfacePoint, _: &WorldUnitVector, _: &WorldUnitVector) -> Option<PositiveReal> { None }
+
+  fn sample_bsdf(
+    &self,
+    hit: &WorldSurfacePoint,
+    out_dir: &WorldUnitVector,
+    mode: TransportMode,
+    sampler: &mut dyn Sampler
+  ) -> Option<BsdfSample> {
+    self.sample_with_ior(hit, out_dir, mode, self.ior.reference(), sampler)
   }
 
-  fn random_bsdf_in_direction(&self) -> &ScatterRandomVariable { &self.scatter_random_var }
+  fn is_dispersive(&self) -> bool { self.ior.is_dispersive() }
+
+  fn sample_bsdf_at_wavelength(
+    &self,
+    hit: &WorldSurfacePoint,
+    out_dir: &WorldUnitVector,
+    mode: TransportMode,
+    wavelength: Real,
+    sampler: &mut dyn Sampler
+  ) -> Option<BsdfSample> {
+    self.sample_with_ior(hit, out_dir, mode, self.ior.at(wavelength), sampler)
+  }
 }
@@ -0,0 +1,113 @@
+use std::{collections::HashMap, sync::Arc};
+
+use serde::Deserialize;
+
+use super::{lambertian::CosineWeightedHemisphere, *};
+use crate::{math::*, raytracing::*, sampling::*, spectrum::Spectrum, textures::*};
+
+#[derive(Debug, Deserialize)]
+struct DiffuseTransmissionParameters {
+  name: String,
+  reflectance: Box<dyn TextureParameters>,
+  transmittance: Box<dyn TextureParameters>
+}
+
+#[typetag::deserialize(name = "diffuse-transmission")]
+impl MaterialParameters for DiffuseTransmissionParameters {
+  fn name(&self) -> String { self.name.clone() }
+
+  fn build_material(&self, _: &HashMap<String, Arc<dyn Material>>) -> Arc<dyn Material> {
+    Arc::new(DiffuseTransmission {
+      reflectance: self.reflectance.build_texture(),
+      transmittance: self.transmittance.build_texture()
+    })
+  }
+}
+
+/// A Lambertian surface which scatters light into both hemispheres, such as a lamp shade or a sheet of paper.
+#[derive(Debug)]
+pub struct DiffuseTransmission {
+  reflectance: Arc<dyn Texture>,
+  transmittance: Arc<dyn Texture>
+}
+
+impl DiffuseTransmission {
+  /// The probability of sampling the reflected (rather than the transmitted) hemisphere, which is proportional to how
+  /// much light each one scatters.
+  fn reflect_probability(&self, hit: &WorldSurfacePoint) -> Real {
+    let reflected = self.reflectance.value(&hit.texture_point()).luminance().max(0.0);
+    let transmitted = self.transmittance.value(&hit.texture_point()).luminance().max(0.0);
+    if reflected + transmitted > 0.0 {
+      reflected / (reflected + transmitted)
+    } else {
+      0.5
+    }
+  }
+}
+
+impl ContinuousRandomVariable for DiffuseTransmission {
+  type Param = ScatterParameter;
+  type Sample = WorldUnitVector;
+
+  fn sample_with_pdf(
+    &self,
+    p @ (hit, out_dir): &Self::Param,
+    sampler: &mut dyn Sampler
+  ) -> Option<(Self::Sample, PositiveReal)> {
+    // Sampling the cosine-weighted hemisphere around the reversed outgoing direction gives a transmitted direction
+    let side = if sampler.next().into_inner() < self.reflect_probability(hit) { *out_dir } else { -*out_dir };
+    let dir = CosineWeightedHemisphere.sample(&(hit.clone(), side), sampler)?;
+    self.pdf(p, &dir).map(|pdf| (dir, pdf))
+  }
+
+  fn pdf(&self, (hit, out_dir): &Self::Param, sample: &Self::Sample) -> Option<PositiveReal> {
+    let reflect_probability = self.reflect_probability(hit);
+    let same_side = sample.dot(&hit.shading_normal) * out_dir.dot(&hit.shading_normal) > 0.0;
+    let side_probability = if same_side { reflect_probability } else { 1.0 - reflect_probability };
+    PositiveReal::new(side_probability * sample.abs_dot(&hit.shading_normal) * INV_PI)
+  }
+}
+
+impl Material for DiffuseTransmission {
+  fn bsdf(
+    &self,
+    hit: &WorldSurfacePoint,
+    in_dir: &WorldUnitVector,
+    out_dir: &WorldUnitVector,
+    mode: TransportMode
+  ) -> Spectrum {
+    let same_side = in_dir.dot(&hit.shading_normal) * out_dir.dot(&hit.shading_normal) > 0.0;
+    let albedo = if same_side { &self.reflectance } else { &self.transmittance };
+    albedo.value(&hit.texture_point()) * shading_normal_correction(hit, in_dir, out_dir, mode) * INV_PI
+  }
+
+  fn bsdf_cos(
+    &self,
+    hit: &WorldSurfacePoint,
+    in_dir: &WorldUnitVector,
+    out_dir: &WorldUnitVector,
+    mode: TransportMode
+  ) -> Spectrum {
+    self.bsdf(hit, in_dir, out_dir, mode) * in_dir.abs_dot(&hit.shading_normal)
+  }
+
+  fn bsdf_pdf(
+    &self,
+    hit: &WorldSurfacePoint,
+    in_dir: &WorldUnitVector,
+    out_dir: &WorldUnitVector
+  ) -> Option<PositiveReal> {
+    self.pdf(&(hit.clone(), *out_dir), in_dir)
+  }
+
+  fn sample_bsdf(
+    &self,
+    hit: &WorldSurfacePoint,
+    out_dir: &WorldUnitVector,
+    mode: TransportMode,
+    sampler: &mut dyn Sampler
+  ) -> Option<BsdfSample> {
+    let (in_dir, pdf) = self.sample_with_pdf(&(hit.clone(), *out_dir), sampler)?;
+    Some(BsdfSample { in_dir, bsdf_cos: self.bsdf_cos(hit, &in_dir, out_dir, mode), pdf, is_delta: false })
+  }
+}
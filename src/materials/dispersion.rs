@@ -0,0 +1,107 @@
+use serde::Deserialize;
+
+use crate::math::Real;
+
+/// The wavelength in nanometers of the sodium D line, at which indices of refraction are conventionally quoted and
+/// which is used when rendering in RGB.
+const REFERENCE_WAVELENGTH: Real = 589.3;
+
+/// Glasses and gems with well known Sellmeier coefficients.
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub enum Glass {
+  #[serde(alias = "bk7")]
+  BK7,
+
+  #[serde(alias = "sf11")]
+  SF11,
+
+  #[serde(alias = "fused-silica")]
+  FusedSilica,
+
+  #[serde(alias = "diamond")]
+  Diamond
+}
+
+impl Glass {
+  fn sellmeier(self) -> ([Real; 3], [Real; 3]) {
+    match self {
+      Glass::BK7 => ([1.039612, 0.2317923, 1.010469], [0.006000699, 0.02001791, 103.5607]),
+      Glass::SF11 => ([1.737597, 0.3137473, 1.898781], [0.01318871, 0.06230681, 155.2363]),
+      Glass::FusedSilica => ([0.6961663, 0.4079426, 0.8974794], [0.004679148, 0.01351206, 97.934]),
+      Glass::Diamond => ([0.3306, 4.3356, 0.0], [0.030625, 0.011236, 0.0])
+    }
+  }
+}
+
+/// An index of refraction given either as a constant, as the coefficients of Cauchy's equation or Sellmeier's equation
+/// (with wavelengths in micrometers), or by the name of a glass.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum IorParameters {
+  Constant(Real),
+  Cauchy { cauchy: Vec<Real> },
+  Sellmeier { sellmeier: SellmeierParameters },
+  Glass { glass: Glass }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct SellmeierParameters {
+  b: [Real; 3],
+  c: [Real; 3]
+}
+
+impl IorParameters {
+  pub fn build_ior(&self) -> IndexOfRefraction {
+    match self {
+      IorParameters::Constant(ior) => IndexOfRefraction::Constant(*ior),
+      IorParameters::Cauchy { cauchy } => {
+        if cauchy.is_empty() {
+          panic!("Cauchy's equation needs at least one coefficient!");
+        }
+
+        IndexOfRefraction::Cauchy(cauchy.clone())
+      },
+      IorParameters::Sellmeier { sellmeier } => IndexOfRefraction::Sellmeier { b: sellmeier.b, c: sellmeier.c },
+      IorParameters::Glass { glass } => {
+        let (b, c) = glass.sellmeier();
+        IndexOfRefraction::Sellmeier { b, c }
+      }
+    }
+  }
+}
+
+#[derive(Clone, Debug)]
+pub enum IndexOfRefraction {
+  Constant(Real),
+
+  /// n = A + B / λ² + C / λ⁴ + ...
+  Cauchy(Vec<Real>),
+
+  /// n² = 1 + Σ Bᵢ λ² / (λ² - Cᵢ)
+  Sellmeier {
+    b: [Real; 3],
+    c: [Real; 3]
+  }
+}
+
+impl IndexOfRefraction {
+  pub fn is_dispersive(&self) -> bool { !matches!(self, IndexOfRefraction::Constant(_)) }
+
+  /// The index of refraction for light of a wavelength in nanometers.
+  pub fn at(&self, wavelength: Real) -> Real {
+    let micrometers_squared = (wavelength / 1000.0) * (wavelength / 1000.0);
+    match self {
+      IndexOfRefraction::Constant(ior) => *ior,
+      IndexOfRefraction::Cauchy(coefficients) => {
+        coefficients.iter().rev().fold(0.0, |n, coefficient| n / micrometers_squared + coefficient)
+      },
+      IndexOfRefraction::Sellmeier { b, c } => {
+        let sum: Real = b.iter().zip(c).map(|(b, c)| b * micrometers_squared / (micrometers_squared - c)).sum();
+        (1.0 + sum).sqrt()
+      }
+    }
+  }
+
+  /// The index of refraction used when wavelengths aren't tracked.
+  pub fn reference(&self) -> Real { self.at(REFERENCE_WAVELENGTH) }
+}
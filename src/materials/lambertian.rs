@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
 use serde::Deserialize;
 
@@ -15,19 +15,16 @@ struct LambertianParameters {
 impl MaterialParameters for LambertianParameters {
   fn name(&self) -> String { self.name.clone() }
 
-  fn build_material(&self) -> Arc<dyn Material> {
-    Arc::new(Lambertian {
-      albedo: self.albedo.build_texture(),
-      scatter_random_var: ScatterRandomVariable::Diffuse(Box::new(CosineWeightedHemisphere))
-    })
+  fn build_material(&self, _: &HashMap<String, Arc<dyn Material>>) -> Arc<dyn Material> {
+    Arc::new(Lambertian { albedo: self.albedo.build_texture() })
   }
 }
 
 #[derive(Debug)]
-struct CosineWeightedHemisphere;
+pub(super) struct CosineWeightedHemisphere;
 
 impl ContinuousRandomVariable for CosineWeightedHemisphere {
-  type Param = (WorldSurfacePoint, WorldUnitVector);
+  type Param = ScatterParameter;
   type Sample = WorldUnitVector;
 
   fn sample_with_pdf(
@@ -45,25 +42,64 @@ impl ContinuousRandomVariable for CosineWeightedHemisphere {
     self.pdf(p, &dir).map(|pdf| (dir, pdf))
   }
 
-  fn pdf(&self, (hit, _): &Self::Param, sample: &Self::Sample) -> Option<PositiveReal> {
-    PositiveReal::new((sample.dot(&hit.shading_normal) * INV_PI).abs())
+  fn pdf(&self, (hit, out_dir): &Self::Param, sample: &Self::Sample) -> Option<PositiveReal> {
+    // Only the hemisphere on the same side as `out_dir` is ever sampled
+    let cos = sample.dot(&hit.shading_normal);
+    if cos * out_dir.dot(&hit.shading_normal) <= 0.0 {
+      return None;
+    }
+
+    PositiveReal::new(cos.abs() * INV_PI)
   }
 }
 
 #[derive(Debug)]
 pub struct Lambertian {
-  albedo: Arc<dyn Texture>,
-  scatter_random_var: ScatterRandomVariable
+  albedo: Arc<dyn Texture>
 }
 
 impl Material for Lambertian {
-  fn bsdf(&self, hit: &WorldSurfacePoint, _: &WorldUnitVector, _: &WorldUnitVector) -> Spectrum {
-    self.albedo.value(&hit.tex_coord) * INV_PI
+  fn bsdf(
+    &self,
+    hit: &WorldSurfacePoint,
+    in_dir: &WorldUnitVector,
+    out_dir: &WorldUnitVector,
+    mode: TransportMode
+  ) -> Spectrum {
+    if in_dir.dot(&hit.shading_normal) * out_dir.dot(&hit.shading_normal) <= 0.0 {
+      return Spectrum::none();
+    }
+
+    self.albedo.value(&hit.texture_point()) * shading_normal_correction(hit, in_dir, out_dir, mode) * INV_PI
+  }
+
+  fn bsdf_cos(
+    &self,
+    hit: &WorldSurfacePoint,
+    in_dir: &WorldUnitVector,
+    out_dir: &WorldUnitVector,
+    mode: TransportMode
+  ) -> Spectrum {
+    self.bsdf(hit, in_dir, out_dir, mode) * in_dir.abs_dot(&hit.shading_normal)
   }
 
-  fn bsdf_cos(&self, hit: &WorldSurfacePoint, in_dir: &WorldUnitVector, _: &WorldUnitVector) -> Spectrum {
-    self.albedo.value(&hit.tex_coord) * in_dir.abs_dot(&hit.shading_normal) * INV_PI
+  fn bsdf_pdf(
+    &self,
+    hit: &WorldSurfacePoint,
+    in_dir: &WorldUnitVector,
+    out_dir: &WorldUnitVector
+  ) -> Option<PositiveReal> {
+    CosineWeightedHemisphere.pdf(&(hit.clone(), *out_dir), in_dir)
   }
 
-  fn random_bsdf_in_direction(&self) -> &ScatterRandomVariable { &self.scatter_random_var }
+  fn sample_bsdf(
+    &self,
+    hit: &WorldSurfacePoint,
+    out_dir: &WorldUnitVector,
+    mode: TransportMode,
+    sampler: &mut dyn Sampler
+  ) -> Option<BsdfSample> {
+    let (in_dir, pdf) = CosineWeightedHemisphere.sample_with_pdf(&(hit.clone(), *out_dir), sampler)?;
+    Some(BsdfSample { in_dir, bsdf_cos: self.bsdf_cos(hit, &in_dir, out_dir, mode), pdf, is_delta: false })
+  }
 }
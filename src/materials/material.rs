@@ -1,22 +1,116 @@
-use std::{fmt::Debug, sync::Arc};
+use std::{collections::HashMap, fmt::Debug, sync::Arc};
 
-use crate::{math::*, raytracing::*, sampling::RandomVariable, spectrum::*};
+use super::Medium;
+use crate::{math::*, raytracing::*, sampling::Sampler, spectrum::*};
 
 #[typetag::deserialize(tag = "type")]
 pub trait MaterialParameters: Debug {
   fn name(&self) -> String;
 
-  fn build_material(&self) -> Arc<dyn Material>;
+  /// Builds the material, where `materials` holds every material declared earlier in the scene file by name.
+  fn build_material(&self, materials: &HashMap<String, Arc<dyn Material>>) -> Arc<dyn Material>;
 }
 
-pub type ScatterRandomVariable = RandomVariable<(WorldSurfacePoint, WorldUnitVector), WorldUnitVector>;
+pub type ScatterParameter = (WorldSurfacePoint, WorldUnitVector);
+
+/// The quantity carried along a path. BSDFs which are not self-adjoint (refraction, or anything shaded with a normal
+/// other than the geometric one) must be evaluated differently depending on which end of the path it started from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransportMode {
+  /// Paths started at the camera, so `out_dir` points back towards the camera.
+  Radiance,
+
+  /// Paths started at a light, so `out_dir` points back towards the light.
+  Importance
+}
+
+/// The factor by which a BSDF evaluated with shading normals must be scaled to account for the asymmetry they
+/// introduce, as derived by Veach. This is always one when transporting radiance.
+pub fn shading_normal_correction(
+  point: &WorldSurfacePoint,
+  in_dir: &WorldUnitVector,
+  out_dir: &WorldUnitVector,
+  mode: TransportMode
+) -> Real {
+  match mode {
+    TransportMode::Radiance => 1.0,
+    TransportMode::Importance => {
+      let numerator = out_dir.abs_dot(&point.shading_normal) * in_dir.abs_dot(&point.geometric_normal);
+      let denominator = out_dir.abs_dot(&point.geometric_normal) * in_dir.abs_dot(&point.shading_normal);
+      if denominator == 0.0 {
+        0.0
+      } else {
+        numerator / denominator
+      }
+    }
+  }
+}
+
+/// A direction sampled from a material's BSDF along with everything needed to weight it. For samples drawn from a
+/// delta lobe, `bsdf_cos` is the lobe's total scattered weight and `pdf` is the discrete probability with which that
+/// lobe was chosen; otherwise `pdf` is a solid-angle density.
+#[derive(Debug, Clone)]
+pub struct BsdfSample {
+  pub in_dir: WorldUnitVector,
+  pub bsdf_cos: Spectrum,
+  pub pdf: PositiveReal,
+  pub is_delta: bool
+}
 
 pub trait Material: Debug {
-  fn bsdf(&self, point: &WorldSurfacePoint, in_dir: &WorldUnitVector, out_dir: &WorldUnitVector) -> Spectrum {
-    self.bsdf_cos(point, in_dir, out_dir) / point.shading_normal.abs_dot(in_dir)
+  fn bsdf(
+    &self,
+    point: &WorldSurfacePoint,
+    in_dir: &WorldUnitVector,
+    out_dir: &WorldUnitVector,
+    mode: TransportMode
+  ) -> Spectrum {
+    self.bsdf_cos(point, in_dir, out_dir, mode) / point.shading_normal.abs_dot(in_dir)
   }
 
-  fn bsdf_cos(&self, point: &WorldSurfacePoint, in_dir: &WorldUnitVector, out_dir: &WorldUnitVector) -> Spectrum;
+  /// Evaluates the non-delta lobes of the BSDF, multiplied by the cosine of `in_dir` with the shading normal.
+  fn bsdf_cos(
+    &self,
+    point: &WorldSurfacePoint,
+    in_dir: &WorldUnitVector,
+    out_dir: &WorldUnitVector,
+    mode: TransportMode
+  ) -> Spectrum;
+
+  /// The solid-angle density with which `sample_bsdf` produces `in_dir` from its non-delta lobes.
+  fn bsdf_pdf(
+    &self,
+    point: &WorldSurfacePoint,
+    in_dir: &WorldUnitVector,
+    out_dir: &WorldUnitVector
+  ) -> Option<PositiveReal>;
+
+  fn sample_bsdf(
+    &self,
+    point: &WorldSurfacePoint,
+    out_dir: &WorldUnitVector,
+    mode: TransportMode,
+    sampler: &mut dyn Sampler
+  ) -> Option<BsdfSample>;
+
+  /// Whether the directions the material scatters light in depend on its wavelength (and not just how much of it is
+  /// scattered), so that each wavelength has to follow its own path.
+  fn is_dispersive(&self) -> bool { false }
+
+  /// Samples the BSDF for light of a single wavelength in nanometers, which only differs from `sample_bsdf` for
+  /// dispersive materials.
+  fn sample_bsdf_at_wavelength(
+    &self,
+    point: &WorldSurfacePoint,
+    out_dir: &WorldUnitVector,
+    mode: TransportMode,
+    _wavelength: Real,
+    sampler: &mut dyn Sampler
+  ) -> Option<BsdfSample> {
+    self.sample_bsdf(point, out_dir, mode, sampler)
+  }
 
-  fn random_bsdf_in_direction(&self) -> &ScatterRandomVariable;
+  /// The medium filling the inside of the (closed) surface, i.e. the side its geometric normal points away from, as
+  /// seen from `point` on its boundary.
+  fn interior(&self, _point: &WorldSurfacePoint) -> Option<Medium> { None }
 }
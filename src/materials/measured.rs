@@ -0,0 +1,141 @@
+use std::{collections::HashMap, sync::Arc};
+
+use nalgebra as na;
+use serde::Deserialize;
+
+use super::{lambertian::CosineWeightedHemisphere, *};
+use crate::{math::*, raytracing::*, sampling::*, spectrum::Spectrum};
+
+#[derive(Debug, Deserialize)]
+struct MeasuredParameters {
+  name: String,
+  filename: String
+}
+
+#[typetag::deserialize(name = "measured")]
+impl MaterialParameters for MeasuredParameters {
+  fn name(&self) -> String { self.name.clone() }
+
+  fn build_material(&self, _: &HashMap<String, Arc<dyn Material>>) -> Arc<dyn Material> {
+    println!("Loading measured BRDF from \"{}\"...", self.filename);
+    let bytes = std::fs::read(&self.filename).expect("Measured BRDF file not found!");
+    Arc::new(Measured::from_merl_binary(&bytes))
+  }
+}
+
+const THETA_HALF_RES: usize = 90;
+const THETA_DIFF_RES: usize = 90;
+const PHI_DIFF_RES: usize = 180;
+const TABLE_SIZE: usize = THETA_HALF_RES * THETA_DIFF_RES * PHI_DIFF_RES;
+
+/// The per-channel factors by which the raw MERL table entries must be scaled
+const CHANNEL_SCALES: [f64; 3] = [1.0 / 1500.0, 1.15 / 1500.0, 1.66 / 1500.0];
+
+/// An isotropic BRDF tabulated in the half/difference angle parameterization of Rusinkiewicz, as measured for the MERL
+/// BRDF database by Matusik et al. Directions are importance sampled from a cosine-weighted hemisphere.
+#[derive(Debug)]
+pub struct Measured {
+  table: Vec<[Real; 3]>
+}
+
+impl Measured {
+  /// Parses the MERL ".binary" format: three little-endian 32-bit integers giving the table dimensions, followed by the
+  /// red, green and blue tables one after another as little-endian 64-bit floats.
+  fn from_merl_binary(bytes: &[u8]) -> Self {
+    let read_i32 = |i: usize| i32::from_le_bytes(bytes[4 * i..4 * i + 4].try_into().unwrap()) as usize;
+    if bytes.len() < 12 || read_i32(0) * read_i32(1) * read_i32(2) != TABLE_SIZE {
+      panic!("Measured BRDF file does not have the dimensions of a MERL BRDF!");
+    }
+
+    if bytes.len() != 12 + 3 * 8 * TABLE_SIZE {
+      panic!("Measured BRDF file is truncated!");
+    }
+
+    let read_f64 = |i: usize| f64::from_le_bytes(bytes[12 + 8 * i..20 + 8 * i].try_into().unwrap());
+    let table = (0..TABLE_SIZE)
+      .map(|i| {
+        // Negative entries mark directions which were never measured
+        let channel = |c: usize| (read_f64(c * TABLE_SIZE + i) * CHANNEL_SCALES[c]).max(0.0) as Real;
+        [channel(0), channel(1), channel(2)]
+      })
+      .collect();
+
+    Self { table }
+  }
+
+  /// Looks up the BRDF for a pair of directions given in a local frame whose z-axis is the surface normal.
+  fn lookup(&self, in_dir: na::Vector3<Real>, out_dir: na::Vector3<Real>) -> Spectrum {
+    let half = (in_dir + out_dir).normalize();
+    let theta_half = half.z.clamp(-1.0, 1.0).acos();
+    let phi_half = half.y.atan2(half.x);
+
+    // Rotate the incident direction so that the half vector becomes the normal, giving the difference vector
+    let z_axis = na::Vector3::z_axis();
+    let y_axis = na::Vector3::y_axis();
+    let diff = na::Rotation3::from_axis_angle(&y_axis, -theta_half)
+      * (na::Rotation3::from_axis_angle(&z_axis, -phi_half) * in_dir);
+    let theta_diff = diff.z.clamp(-1.0, 1.0).acos();
+    let mut phi_diff = diff.y.atan2(diff.x);
+
+    // Reciprocity lets us fold phi_diff into [0, pi)
+    if phi_diff < 0.0 {
+      phi_diff += PI;
+    }
+
+    // The theta_half axis is sampled non-linearly to concentrate samples near the specular peak
+    let theta_half_index = ((theta_half / (PI / 2.0)).max(0.0).sqrt() * THETA_HALF_RES as Real) as usize;
+    let theta_diff_index = (theta_diff / (PI / 2.0) * THETA_DIFF_RES as Real) as usize;
+    let phi_diff_index = (phi_diff / PI * PHI_DIFF_RES as Real) as usize;
+
+    let index = phi_diff_index.min(PHI_DIFF_RES - 1)
+      + PHI_DIFF_RES * theta_diff_index.min(THETA_DIFF_RES - 1)
+      + PHI_DIFF_RES * THETA_DIFF_RES * theta_half_index.min(THETA_HALF_RES - 1);
+
+    let [r, g, b] = self.table[index];
+    Spectrum::new(r, g, b)
+  }
+}
+
+impl Material for Measured {
+  fn bsdf_cos(
+    &self,
+    hit: &WorldSurfacePoint,
+    in_dir: &WorldUnitVector,
+    out_dir: &WorldUnitVector,
+    mode: TransportMode
+  ) -> Spectrum {
+    let mut normal = hit.shading_normal;
+    if normal.dot(out_dir) < 0.0 {
+      normal = -normal;
+    }
+
+    let cos_in = normal.dot(in_dir);
+    if cos_in <= 0.0 {
+      return Spectrum::none();
+    }
+
+    let (tangent, bitangent) = normal.orthonormal_basis();
+    let to_local = |d: &WorldUnitVector| na::vector![d.dot(&tangent), d.dot(&bitangent), d.dot(&normal)];
+    self.lookup(to_local(in_dir), to_local(out_dir)) * cos_in * shading_normal_correction(hit, in_dir, out_dir, mode)
+  }
+
+  fn bsdf_pdf(
+    &self,
+    hit: &WorldSurfacePoint,
+    in_dir: &WorldUnitVector,
+    out_dir: &WorldUnitVector
+  ) -> Option<PositiveReal> {
+    CosineWeightedHemisphere.pdf(&(hit.clone(), *out_dir), in_dir)
+  }
+
+  fn sample_bsdf(
+    &self,
+    hit: &WorldSurfacePoint,
+    out_dir: &WorldUnitVector,
+    mode: TransportMode,
+    sampler: &mut dyn Sampler
+  ) -> Option<BsdfSample> {
+    let (in_dir, pdf) = CosineWeightedHemisphere.sample_with_pdf(&(hit.clone(), *out_dir), sampler)?;
+    Some(BsdfSample { in_dir, bsdf_cos: self.bsdf_cos(hit, &in_dir, out_dir, mode), pdf, is_delta: false })
+  }
+}
@@ -0,0 +1,83 @@
+use crate::{math::*, sampling::Sampler, spectrum::Spectrum};
+
+/// The outcome of tracing a ray segment through a medium up to the next surface it hits.
+#[derive(Debug, Clone)]
+pub enum MediumEvent {
+  /// The ray scattered `dist` along the segment, where `weight` is the scattering coefficient times the transmittance
+  /// up to that point and `pdf` is the density with which that distance was sampled.
+  Scatter { dist: Real, weight: Spectrum, pdf: PositiveReal },
+
+  /// The ray reached the end of the segment, where `weight` is the transmittance along it and `probability` is the
+  /// chance of not having scattered before then.
+  Pass { weight: Spectrum, probability: PositiveReal }
+}
+
+/// A homogeneous participating medium with a Henyey-Greenstein phase function, filling the inside of a closed surface.
+#[derive(Debug, Clone)]
+pub struct Medium {
+  /// The fraction of each interaction which scatters rather than absorbs light.
+  pub albedo: Spectrum,
+
+  /// The average distance light travels between interactions, per channel.
+  pub mean_free_path: Spectrum,
+
+  /// The average cosine of the angle light is deflected by at each scattering event.
+  pub anisotropy: Real
+}
+
+impl Medium {
+  fn extinction(&self) -> [Real; 3] {
+    let mfp = &self.mean_free_path;
+    [mfp.r(), mfp.g(), mfp.b()].map(|d| 1.0 / d.max(1e-6))
+  }
+
+  fn transmittance(extinction: &[Real; 3], dist: Real) -> Spectrum {
+    Spectrum::new((-extinction[0] * dist).exp(), (-extinction[1] * dist).exp(), (-extinction[2] * dist).exp())
+  }
+
+  /// Samples where a ray first interacts with the medium along a segment of length `max_dist`. Distances are drawn by
+  /// picking a channel uniformly and sampling its exponential distribution, so the densities of all three channels are
+  /// averaged.
+  pub fn sample_event(&self, max_dist: Real, sampler: &mut dyn Sampler) -> Option<MediumEvent> {
+    let extinction = self.extinction();
+    let channel = ((sampler.next().into_inner() * 3.0) as usize).min(2);
+    let dist = -(1.0 - sampler.next().into_inner()).ln() / extinction[channel];
+
+    if dist < max_dist {
+      let transmittance = Self::transmittance(&extinction, dist);
+      let density = Spectrum::new(extinction[0], extinction[1], extinction[2]) * transmittance;
+      let pdf = (density.r() + density.g() + density.b()) / 3.0;
+      Some(MediumEvent::Scatter { dist, weight: self.albedo * density, pdf: PositiveReal::new(pdf)? })
+    } else {
+      let transmittance = Self::transmittance(&extinction, max_dist);
+      let probability = (transmittance.r() + transmittance.g() + transmittance.b()) / 3.0;
+      Some(MediumEvent::Pass { weight: transmittance, probability: PositiveReal::new(probability)? })
+    }
+  }
+
+  /// Samples the direction a ray travelling along `dir` is scattered into. The phase function is its own density, so
+  /// the returned value is both.
+  pub fn sample_phase(
+    &self,
+    dir: &WorldUnitVector,
+    sampler: &mut dyn Sampler
+  ) -> Option<(WorldUnitVector, PositiveReal)> {
+    let g = self.anisotropy.clamp(-0.99, 0.99);
+    let u = sampler.next().into_inner();
+    let cos_theta = if g.abs() < 1e-3 {
+      1.0 - 2.0 * u
+    } else {
+      let s = (1.0 - g * g) / (1.0 - g + 2.0 * g * u);
+      ((1.0 + g * g - s * s) / (2.0 * g)).clamp(-1.0, 1.0)
+    };
+
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * PI * sampler.next().into_inner();
+    let (tangent, bitangent) = dir.orthonormal_basis();
+    let scattered =
+      (tangent * (sin_theta * phi.cos()) + bitangent * (sin_theta * phi.sin()) + *dir * cos_theta).normalize();
+
+    let denominator = 1.0 + g * g - 2.0 * g * cos_theta;
+    PositiveReal::new((1.0 - g * g) / (4.0 * PI * denominator * denominator.sqrt())).map(|pdf| (scattered, pdf))
+  }
+}
@@ -0,0 +1,182 @@
+use std::{collections::HashMap, sync::Arc};
+
+use serde::Deserialize;
+
+use super::*;
+use crate::{math::*, raytracing::*, sampling::*, spectrum::Spectrum, textures::*};
+
+#[derive(Debug, Deserialize)]
+struct MicrofacetParameters {
+  name: String,
+  albedo: Box<dyn TextureParameters>,
+
+  #[serde(alias = "alpha-x")]
+  alpha_x: Real,
+
+  #[serde(alias = "alpha-y")]
+  alpha_y: Real,
+  rotation: Option<Box<dyn TextureParameters>>
+}
+
+#[typetag::deserialize(name = "microfacet")]
+impl MaterialParameters for MicrofacetParameters {
+  fn name(&self) -> String { self.name.clone() }
+
+  fn build_material(&self, _: &HashMap<String, Arc<dyn Material>>) -> Arc<dyn Material> {
+    // Perfectly smooth distributions are degenerate, so clamp to something which is visually indistinguishable
+    Arc::new(Microfacet {
+      albedo: self.albedo.build_texture(),
+      alpha_x: self.alpha_x.max(1e-3),
+      alpha_y: self.alpha_y.max(1e-3),
+      rotation: self.rotation.as_ref().map(|r| r.build_texture())
+    })
+  }
+}
+
+/// A conductor-like reflector with an anisotropic GGX distribution of microfacet normals, whose roughness is `alpha_x`
+/// along the shading tangent and `alpha_y` along the bitangent. The albedo is the reflectance at normal incidence for
+/// Schlick's Fresnel approximation, and the luminance of the optional rotation texture turns the tangent about the
+/// normal by that many full turns.
+#[derive(Debug)]
+pub struct Microfacet {
+  albedo: Arc<dyn Texture>,
+  alpha_x: Real,
+  alpha_y: Real,
+  rotation: Option<Arc<dyn Texture>>
+}
+
+type LocalVector = nalgebra::Vector3<Real>;
+
+impl Microfacet {
+  /// The shading frame at `hit`, rotated by the rotation texture and flipped to the side of `out_dir`.
+  fn frame(&self, hit: &WorldSurfacePoint, out_dir: &WorldUnitVector) -> ShadingFrame {
+    let mut frame = hit.shading_frame();
+    if let Some(rotation) = &self.rotation {
+      frame = frame.rotated(rotation.value(&hit.texture_point()).luminance() * 2.0 * PI);
+    }
+
+    if out_dir.dot(&frame.normal) < 0.0 {
+      frame = frame.flipped();
+    }
+
+    frame
+  }
+
+  fn local(frame: &ShadingFrame, v: &WorldUnitVector) -> LocalVector { frame.to_local(v).inner().into_inner() }
+
+  /// The GGX normal distribution function.
+  fn distribution(&self, m: &LocalVector) -> Real {
+    let (x, y) = (m.x / self.alpha_x, m.y / self.alpha_y);
+    let denominator = x * x + y * y + m.z * m.z;
+    INV_PI / (self.alpha_x * self.alpha_y * denominator * denominator)
+  }
+
+  /// Smith's auxiliary function for GGX, from which the masking and shadowing terms are built.
+  fn lambda(&self, w: &LocalVector) -> Real {
+    let (x, y) = (self.alpha_x * w.x, self.alpha_y * w.y);
+    (-1.0 + (1.0 + (x * x + y * y) / (w.z * w.z)).sqrt()) / 2.0
+  }
+
+  /// Samples a microfacet normal from the distribution of normals visible from `w`, following Heitz (2018).
+  fn sample_visible_normal(&self, w: &LocalVector, sampler: &mut dyn Sampler) -> LocalVector {
+    // Stretch the view direction so the distribution becomes that of a hemisphere with unit roughness
+    let wh = LocalVector::new(self.alpha_x * w.x, self.alpha_y * w.y, w.z).normalize();
+    let length_squared = wh.x * wh.x + wh.y * wh.y;
+    let t1 = if length_squared > 0.0 {
+      LocalVector::new(-wh.y, wh.x, 0.0) / length_squared.sqrt()
+    } else {
+      LocalVector::new(1.0, 0.0, 0.0)
+    };
+    let t2 = wh.cross(&t1);
+
+    // Sample a disk, warped towards the part of it which projects onto the visible half of the hemisphere
+    let r = sampler.next().into_inner().sqrt();
+    let phi = 2.0 * PI * sampler.next().into_inner();
+    let p1 = r * phi.cos();
+    let s = (1.0 + wh.z) / 2.0;
+    let p2 = (1.0 - s) * (1.0 - p1 * p1).max(0.0).sqrt() + s * r * phi.sin();
+    let nh = t1 * p1 + t2 * p2 + wh * (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt();
+
+    // Unstretch back into the space of the original distribution
+    LocalVector::new(self.alpha_x * nh.x, self.alpha_y * nh.y, nh.z.max(1e-6)).normalize()
+  }
+
+  fn fresnel(&self, hit: &WorldSurfacePoint, cos_theta: Real) -> Spectrum {
+    let f0 = self.albedo.value(&hit.texture_point());
+    let weight = (1.0 - cos_theta.clamp(0.0, 1.0)).powi(5);
+    f0 * (1.0 - weight) + Spectrum::white() * weight
+  }
+}
+
+impl Material for Microfacet {
+  fn bsdf_cos(
+    &self,
+    hit: &WorldSurfacePoint,
+    in_dir: &WorldUnitVector,
+    out_dir: &WorldUnitVector,
+    mode: TransportMode
+  ) -> Spectrum {
+    let frame = self.frame(hit, out_dir);
+    let (wo, wi) = (Self::local(&frame, out_dir), Self::local(&frame, in_dir));
+    if wo.z <= 0.0 || wi.z <= 0.0 {
+      return Spectrum::none();
+    }
+
+    let half = wo + wi;
+    if half.norm_squared() == 0.0 {
+      return Spectrum::none();
+    }
+
+    let m = half.normalize();
+    let masking_shadowing = 1.0 / (1.0 + self.lambda(&wo) + self.lambda(&wi));
+    self.fresnel(hit, wi.dot(&m))
+      * (self.distribution(&m) * masking_shadowing / (4.0 * wo.z))
+      * shading_normal_correction(hit, in_dir, out_dir, mode)
+  }
+
+  fn bsdf_pdf(
+    &self,
+    hit: &WorldSurfacePoint,
+    in_dir: &WorldUnitVector,
+    out_dir: &WorldUnitVector
+  ) -> Option<PositiveReal> {
+    let frame = self.frame(hit, out_dir);
+    let (wo, wi) = (Self::local(&frame, out_dir), Self::local(&frame, in_dir));
+    if wo.z <= 0.0 || wi.z <= 0.0 {
+      return None;
+    }
+
+    // The density of visible normals, transformed into a density of reflected directions
+    let m = (wo + wi).try_normalize(0.0)?;
+    let masking = 1.0 / (1.0 + self.lambda(&wo));
+    PositiveReal::new(masking * self.distribution(&m) / (4.0 * wo.z))
+  }
+
+  fn sample_bsdf(
+    &self,
+    hit: &WorldSurfacePoint,
+    out_dir: &WorldUnitVector,
+    mode: TransportMode,
+    sampler: &mut dyn Sampler
+  ) -> Option<BsdfSample> {
+    let frame = self.frame(hit, out_dir);
+    let wo = Self::local(&frame, out_dir);
+    if wo.z <= 0.0 {
+      return None;
+    }
+
+    let m = self.sample_visible_normal(&wo, sampler);
+    let wi = m * (2.0 * wo.dot(&m)) - wo;
+    if wi.z <= 0.0 {
+      return None;
+    }
+
+    let in_dir = frame.to_world(&UnitVector3::from_raw(wi));
+    Some(BsdfSample {
+      in_dir,
+      bsdf_cos: self.bsdf_cos(hit, &in_dir, out_dir, mode),
+      pdf: self.bsdf_pdf(hit, &in_dir, out_dir)?,
+      is_delta: false
+    })
+  }
+}
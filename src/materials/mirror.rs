@@ -1,9 +1,15 @@
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
 use serde::Deserialize;
 
 use super::*;
-use crate::{math::WorldUnitVector, raytracing::*, sampling::*, spectrum::Spectrum, textures::*};
+use crate::{
+  math::{PositiveReal, WorldUnitVector},
+  raytracing::*,
+  sampling::*,
+  spectrum::Spectrum,
+  textures::*
+};
 
 #[derive(Debug, Deserialize)]
 struct MirrorParameters {
@@ -15,11 +21,8 @@ struct MirrorParameters {
 impl MaterialParameters for MirrorParameters {
   fn name(&self) -> String { self.name.clone() }
 
-  fn build_material(&self) -> Arc<dyn Material> {
-    Arc::new(Mirror {
-      albedo: self.albedo.build_texture(),
-      scatter_random_var: ScatterRandomVariable::Specular(Box::new(ReflectRandomVariable))
-    })
+  fn build_material(&self, _: &HashMap<String, Arc<dyn Material>>) -> Arc<dyn Material> {
+    Arc::new(Mirror { albedo: self.albedo.build_texture() })
   }
 }
 
@@ -27,7 +30,7 @@ impl MaterialParameters for MirrorParameters {
 struct ReflectRandomVariable;
 
 impl DiscreteRandomVariable for ReflectRandomVariable {
-  type Param = (WorldSurfacePoint, WorldUnitVector);
+  type Param = ScatterParameter;
   type Sample = WorldUnitVector;
 
   fn sample(&self, (hit, out_dir): &Self::Param, _: &mut dyn Sampler) -> Option<WorldUnitVector> {
@@ -37,14 +40,25 @@ impl DiscreteRandomVariable for ReflectRandomVariable {
 
 #[derive(Debug)]
 pub struct Mirror {
-  albedo: Arc<dyn Texture>,
-  scatter_random_var: ScatterRandomVariable
+  albedo: Arc<dyn Texture>
 }
 
 impl Material for Mirror {
-  fn bsdf_cos(&self, hit: &WorldSurfacePoint, _: &WorldUnitVector, _: &WorldUnitVector) -> Spectrum {
-    self.albedo.value(&hit.tex_coord)
+  fn bsdf_cos(&self, _: &WorldSurfacePoint, _: &WorldUnitVector, _: &WorldUnitVector, _: TransportMode) -> Spectrum {
+    Spectrum::none()
   }
 
-  fn random_bsdf_in_direction(&self) -> &ScatterRandomVariable { &self.scatter_random_var }
+  fn bsdf_pdf(&self, _: &WorldSurfacePoint, _: &WorldUnitVector, _: &WorldUnitVector) -> Option<PositiveReal> { None }
+
+  fn sample_bsdf(
+    &self,
+    hit: &WorldSurfacePoint,
+    out_dir: &WorldUnitVector,
+    mode: TransportMode,
+    sampler: &mut dyn Sampler
+  ) -> Option<BsdfSample> {
+    let in_dir = ReflectRandomVariable.sample(&(hit.clone(), *out_dir), sampler)?;
+    let bsdf_cos = self.albedo.value(&hit.texture_point()) * shading_normal_correction(hit, &in_dir, out_dir, mode);
+    Some(BsdfSample { in_dir, bsdf_cos, pdf: PositiveReal::ONE, is_delta: true })
+  }
 }
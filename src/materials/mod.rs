@@ -1,8 +1,20 @@
+mod blend;
+mod bump_map;
 mod dieletric;
+mod diffuse_transmission;
+mod dispersion;
 mod lambertian;
 mod material;
+mod measured;
+mod medium;
+mod microfacet;
 mod mirror;
+mod normal_map;
 mod null_material;
+mod subsurface;
+mod thin_dielectric;
 
+pub use dispersion::*;
 pub use material::*;
+pub use medium::*;
 pub use null_material::*;
@@ -0,0 +1,98 @@
+use std::{collections::HashMap, sync::Arc};
+
+use serde::Deserialize;
+
+use super::*;
+use crate::{
+  math::{PositiveReal, Real, UnitVector3, VectorLike, WorldUnitVector},
+  raytracing::*,
+  sampling::*,
+  spectrum::Spectrum,
+  textures::*
+};
+
+#[derive(Debug, Deserialize)]
+struct NormalMapParameters {
+  name: String,
+  material: String,
+  normals: Box<dyn TextureParameters>
+}
+
+#[typetag::deserialize(name = "normal-map")]
+impl MaterialParameters for NormalMapParameters {
+  fn name(&self) -> String { self.name.clone() }
+
+  fn build_material(&self, materials: &HashMap<String, Arc<dyn Material>>) -> Arc<dyn Material> {
+    Arc::new(NormalMap {
+      material: materials
+        .get(&self.material)
+        .unwrap_or_else(|| panic!("Material \"{}\" must be declared before \"{}\"!", self.material, self.name))
+        .clone(),
+      normals: self.normals.build_texture()
+    })
+  }
+}
+
+/// Replaces the shading normal of another material with one read from a tangent-space normal map, where the red,
+/// green and blue channels respectively hold the components along the axes of the original shading frame.
+#[derive(Debug)]
+pub struct NormalMap {
+  material: Arc<dyn Material>,
+  normals: Arc<dyn Texture>
+}
+
+impl NormalMap {
+  fn perturb(&self, hit: &WorldSurfacePoint) -> WorldSurfacePoint {
+    // Remap each channel from [0, 1] to [-1, 1]
+    let rgb = self.normals.value(&hit.texture_point());
+    let local = UnitVector3::from_array([rgb.r() * 2.0 - 1.0, rgb.g() * 2.0 - 1.0, rgb.b() * 2.0 - 1.0]);
+    let mapped = hit.shading_frame().to_world(&local);
+    WorldSurfacePoint { shading_normal: mapped, ..hit.clone() }
+  }
+}
+
+impl Material for NormalMap {
+  fn bsdf_cos(
+    &self,
+    hit: &WorldSurfacePoint,
+    in_dir: &WorldUnitVector,
+    out_dir: &WorldUnitVector,
+    mode: TransportMode
+  ) -> Spectrum {
+    self.material.bsdf_cos(&self.perturb(hit), in_dir, out_dir, mode)
+  }
+
+  fn bsdf_pdf(
+    &self,
+    hit: &WorldSurfacePoint,
+    in_dir: &WorldUnitVector,
+    out_dir: &WorldUnitVector
+  ) -> Option<PositiveReal> {
+    self.material.bsdf_pdf(&self.perturb(hit), in_dir, out_dir)
+  }
+
+  fn sample_bsdf(
+    &self,
+    hit: &WorldSurfacePoint,
+    out_dir: &WorldUnitVector,
+    mode: TransportMode,
+    sampler: &mut dyn Sampler
+  ) -> Option<BsdfSample> {
+    self.material.sample_bsdf(&self.perturb(hit), out_dir, mode, sampler)
+  }
+
+  fn is_dispersive(&self) -> bool { self.material.is_dispersive() }
+
+  fn sample_bsdf_at_wavelength(
+    &self,
+    hit: &WorldSurfacePoint,
+    out_dir: &WorldUnitVector,
+    mode: TransportMode,
+    wavelength: Real,
+    sampler: &mut dyn Sampler
+  ) -> Option<BsdfSample> {
+    self.material.sample_bsdf_at_wavelength(&self.perturb(hit), out_dir, mode, wavelength, sampler)
+  }
+
+  fn interior(&self, hit: &WorldSurfacePoint) -> Option<Medium> { self.material.interior(hit) }
+}
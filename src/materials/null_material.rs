@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
 use serde::Deserialize;
 
@@ -15,38 +15,30 @@ struct NullMaterialParameters {
   name: String
 }
 
-#[typetag::deserialize(name = "mirror")]
+#[typetag::deserialize(name = "null")]
 impl MaterialParameters for NullMaterialParameters {
   fn name(&self) -> String { self.name.clone() }
 
-  fn build_material(&self) -> Arc<dyn Material> { Arc::new(NullMaterial::default()) }
+  fn build_material(&self, _: &HashMap<String, Arc<dyn Material>>) -> Arc<dyn Material> { Arc::new(NullMaterial) }
 }
 
 #[derive(Debug)]
-struct NullRandomVariable;
-
-#[derive(Debug)]
-pub struct NullMaterial {
-  scatter_random_var: ScatterRandomVariable
-}
-
-impl Default for NullMaterial {
-  fn default() -> Self { Self { scatter_random_var: ScatterRandomVariable::Diffuse(Box::new(NullRandomVariable)) } }
-}
+pub struct NullMaterial;
 
 impl Material for NullMaterial {
-  fn bsdf_cos(&self, _: &WorldSurfacePoint, _: &WorldUnitVector, _: &WorldUnitVector) -> Spectrum { Spectrum::none() }
-
-  fn random_bsdf_in_direction(&self) -> &ScatterRandomVariable { &self.scatter_random_var }
-}
-
-impl ContinuousRandomVariable for NullRandomVariable {
-  type Param = (WorldSurfacePoint, WorldUnitVector);
-  type Sample = WorldUnitVector;
-
-  fn sample(&self, _: &Self::Param, _: &mut dyn Sampler) -> Option<Self::Sample> { None }
-
-  fn sample_with_pdf(&self, _: &Self::Param, _: &mut dyn Sampler) -> Option<(Self::Sample, PositiveReal)> { None }
-
-  fn pdf(&self, _: &Self::Param, _: &Self::Sample) -> Option<PositiveReal> { None }
+  fn bsdf_cos(&self, _: &WorldSurfacePoint, _: &WorldUnitVector, _: &WorldUnitVector, _: TransportMode) -> Spectrum {
+    Spectrum::none()
+  }
+
+  fn bsdf_pdf(&self, _: &WorldSurfacePoint, _: &WorldUnitVector, _: &WorldUnitVector) -> Option<PositiveReal> { None }
+
+  fn sample_bsdf(
+    &self,
+    _: &WorldSurfacePoint,
+    _: &WorldUnitVector,
+    _: TransportMode,
+    _: &mut dyn Sampler
+  ) -> Option<BsdfSample> {
+    None
+  }
 }
@@ -0,0 +1,77 @@
+use std::{collections::HashMap, sync::Arc};
+
+use serde::Deserialize;
+
+use super::{dieletric::Dieletric, *};
+use crate::{math::*, raytracing::*, sampling::*, spectrum::Spectrum, textures::*};
+
+fn default_ior() -> Real { 1.33 }
+
+#[derive(Debug, Deserialize)]
+struct SubsurfaceParameters {
+  name: String,
+  albedo: Box<dyn TextureParameters>,
+
+  #[serde(alias = "mean-free-path")]
+  mean_free_path: Box<dyn TextureParameters>,
+
+  #[serde(default)]
+  anisotropy: Real,
+
+  #[serde(default = "default_ior")]
+  ior: Real
+}
+
+#[typetag::deserialize(name = "subsurface")]
+impl MaterialParameters for SubsurfaceParameters {
+  fn name(&self) -> String { self.name.clone() }
+
+  fn build_material(&self, _: &HashMap<String, Arc<dyn Material>>) -> Arc<dyn Material> {
+    Arc::new(Subsurface {
+      albedo: self.albedo.build_texture(),
+      mean_free_path: self.mean_free_path.build_texture(),
+      anisotropy: self.anisotropy,
+      boundary: Dieletric::new(
+        Arc::new(ConstantTexture::new(Spectrum::white(), 1.0)),
+        IndexOfRefraction::Constant(self.ior)
+      )
+    })
+  }
+}
+
+/// A smooth dielectric boundary enclosing a scattering medium, rendered by random walks through the inside of the
+/// surface. The albedo and mean free path (in scene units) are looked up at the boundary point a walk segment ends on,
+/// so surfaces using this material should be closed with outward-facing normals.
+#[derive(Debug)]
+pub struct Subsurface {
+  albedo: Arc<dyn Texture>,
+  mean_free_path: Arc<dyn Texture>,
+  anisotropy: Real,
+  boundary: Dieletric
+}
+
+impl Material for Subsurface {
+  fn bsdf_cos(&self, _: &WorldSurfacePoint, _: &WorldUnitVector, _: &WorldUnitVector, _: TransportMode) -> Spectrum {
+    Spectrum::none()
+  }
+
+  fn bsdf_pdf(&self, _: &WorldSurfacePoint, _: &WorldUnitVector, _: &WorldUnitVector) -> Option<PositiveReal> { None }
+
+  fn sample_bsdf(
+    &self,
+    hit: &WorldSurfacePoint,
+    out_dir: &WorldUnitVector,
+    mode: TransportMode,
+    sampler: &mut dyn Sampler
+  ) -> Option<BsdfSample> {
+    self.boundary.sample_bsdf(hit, out_dir, mode, sampler)
+  }
+
+  fn interior(&self, hit: &WorldSurfacePoint) -> Option<Medium> {
+    Some(Medium {
+      albedo: self.albedo.value(&hit.texture_point()),
+      mean_free_path: self.mean_free_path.value(&hit.texture_point()),
+      anisotropy: self.anisotropy
+    })
+  }
+}
@@ -0,0 +1,99 @@
+use std::{collections::HashMap, sync::Arc};
+
+use serde::Deserialize;
+
+use super::{dieletric::fresnel_reflectance, *};
+use crate::{math::*, raytracing::*, sampling::*, spectrum::Spectrum, textures::*};
+
+#[derive(Debug, Deserialize)]
+struct ThinDielectricParameters {
+  name: String,
+  albedo: Box<dyn TextureParameters>,
+  ior: Real
+}
+
+#[typetag::deserialize(name = "thin-dielectric")]
+impl MaterialParameters for ThinDielectricParameters {
+  fn name(&self) -> String { self.name.clone() }
+
+  fn build_material(&self, _: &HashMap<String, Arc<dyn Material>>) -> Arc<dyn Material> {
+    Arc::new(ThinDielectric {
+      albedo: self.albedo.build_texture(),
+      scatter_random_var: ThinSlabRandomVariable { index_of_refraction: self.ior }
+    })
+  }
+}
+
+#[derive(Debug)]
+struct ThinSlabRandomVariable {
+  index_of_refraction: Real
+}
+
+impl DiscreteRandomVariable for ThinSlabRandomVariable {
+  type Param = ScatterParameter;
+  /// The scattered direction along with the probability of having chosen it
+  type Sample = (WorldUnitVector, PositiveReal);
+
+  fn sample(&self, (hit, out_dir): &Self::Param, sampler: &mut dyn Sampler) -> Option<Self::Sample> {
+    let mut normal = hit.shading_normal;
+    if out_dir.dot(&normal) < 0.0 {
+      normal = -normal;
+    }
+
+    // A ray can never be totally internally reflected upon entering the slab, and it exits at the angle it entered
+    let eta = self.index_of_refraction;
+    let cos_theta_in = out_dir.dot(&normal);
+    let sin_theta_out_squared = (1.0 - cos_theta_in * cos_theta_in) / (eta * eta);
+    let cos_theta_out = (1.0 - sin_theta_out_squared).max(0.0).sqrt();
+    let single_reflectance = fresnel_reflectance(cos_theta_in, cos_theta_out, 1.0, eta);
+
+    // Sum the geometric series of all the paths bouncing back and forth between the two faces of the slab
+    let single_transmittance = 1.0 - single_reflectance;
+    let reflect_probability = if single_reflectance < 1.0 {
+      single_reflectance
+        + single_transmittance * single_transmittance * single_reflectance
+          / (1.0 - single_reflectance * single_reflectance)
+    } else {
+      1.0
+    };
+
+    if sampler.next().into_inner() < reflect_probability {
+      PositiveReal::new(reflect_probability).map(|p| (out_dir.reflect_about(normal), p))
+    } else {
+      PositiveReal::new(1.0 - reflect_probability).map(|p| (-*out_dir, p))
+    }
+  }
+}
+
+/// A dielectric slab too thin to displace the rays passing through it, such as a window pane. Since both faces are
+/// parallel, transmitted rays leave in the direction they arrived.
+#[derive(Debug)]
+pub struct ThinDielectric {
+  albedo: Arc<dyn Texture>,
+  scatter_random_var: ThinSlabRandomVariable
+}
+
+impl Material for ThinDielectric {
+  fn bsdf_cos(&self, _: &WorldSurfacePoint, _: &WorldUnitVector, _: &WorldUnitVector, _: TransportMode) -> Spectrum {
+    Spectrum::none()
+  }
+
+  fn bsdf_pdf(&self, _: &WorldSurfacePoint, _: &WorldUnitVector, _: &WorldUnitVector) -> Option<PositiveReal> { None }
+
+  fn sample_bsdf(
+    &self,
+    hit: &WorldSurfacePoint,
+    out_dir: &WorldUnitVector,
+    mode: TransportMode,
+    sampler: &mut dyn Sampler
+  ) -> Option<BsdfSample> {
+    let (in_dir, probability) = self.scatter_random_var.sample(&(hit.clone(), *out_dir), sampler)?;
+    let weight = probability.into_inner() * shading_normal_correction(hit, &in_dir, out_dir, mode);
+    Some(BsdfSample {
+      in_dir,
+      bsdf_cos: self.albedo.value(&hit.texture_point()) * weight,
+      pdf: probability,
+      is_delta: true
+    })
+  }
+}
@@ -26,6 +26,13 @@ pub trait Transform<In: Space<3>, Out: Space<3>>: Debug + Display + Sync + Send
       self.point(&ray.origin()),
       transformed_dir
     )
+    .with_kind(ray.kind())
+    .with_differentials(ray.differentials().map(|d| RayDifferentials {
+      x_origin: self.point(&d.x_origin),
+      x_dir: self.direction(&d.x_dir),
+      y_origin: self.point(&d.y_origin),
+      y_dir: self.direction(&d.y_dir)
+    }))
   }
 
   fn inverse_vector(&self, vector: &Vector3<Out>) -> Vector3<In>;
@@ -45,6 +52,13 @@ pub trait Transform<In: Space<3>, Out: Space<3>>: Debug + Display + Sync + Send
       self.inverse_point(&ray.origin()),
       transformed_dir
     )
+    .with_kind(ray.kind())
+    .with_differentials(ray.differentials().map(|d| RayDifferentials {
+      x_origin: self.inverse_point(&d.x_origin),
+      x_dir: self.inverse_direction(&d.x_dir),
+      y_origin: self.inverse_point(&d.y_origin),
+      y_dir: self.inverse_direction(&d.y_dir)
+    }))
   }
 }
 
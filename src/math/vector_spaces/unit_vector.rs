@@ -25,6 +25,20 @@ where Const<D>: ToTypenum
   }
 }
 
+impl<S: Space<3>> UnitVector<3, S> {
+  /// Returns two unit vectors which, together with this one, form a right-handed orthonormal basis. This uses the
+  /// branchless construction of Duff et al., "Building an Orthonormal Basis, Revisited" (2017).
+  pub fn orthonormal_basis(&self) -> (Self, Self) {
+    let n = self.inner.into_inner();
+    let sign = (1.0 as Real).copysign(n.z);
+    let a = -1.0 / (sign + n.z);
+    let b = n.x * n.y * a;
+    let t = na::Unit::new_unchecked(na::vector![1.0 + sign * n.x * n.x * a, sign * b, -sign * n.x]);
+    let s = na::Unit::new_unchecked(na::vector![b, sign + n.y * n.y * a, -n.y]);
+    (t.into(), s.into())
+  }
+}
+
 impl<const D: usize, S: Space<D>> Wrapper<na::Unit<na::SVector<Real, D>>> for UnitVector<D, S>
 where Const<D>: ToTypenum
 {
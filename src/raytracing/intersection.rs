@@ -1,20 +1,146 @@
-use crate::{lights::Light, materials::Material, math::*, textures::TextureCoordinate};
+use super::*;
+use crate::{
+  lights::Light,
+  materials::Material,
+  math::*,
+  surfaces::Visibility,
+  textures::{TextureCoordinate, TextureFootprint, TexturePoint}
+};
+
+/// The space a surface was modelled in, before its transform placed it in the world.
+#[derive(Debug, Clone, Copy)]
+pub struct ObjectSpace;
+
+impl Space<3> for ObjectSpace {}
+
+pub type ObjectPoint = Point3<ObjectSpace>;
+
+pub type ObjectVector = Vector3<ObjectSpace>;
 
 #[derive(Debug, Clone)]
 pub struct SurfacePoint<S: Space<3>> {
   pub point: Point3<S>,
   pub geometric_normal: UnitVector3<S>,
   pub shading_normal: UnitVector3<S>,
-  pub tex_coord: TextureCoordinate
+  pub tex_coord: TextureCoordinate,
+
+  /// The direction of increasing u, which need not be exactly orthogonal to the shading normal (see `shading_frame`)
+  pub shading_tangent: UnitVector3<S>,
+
+  /// The partial derivatives of `point` with respect to the two texture coordinates
+  pub dpdu: Vector3<S>,
+  pub dpdv: Vector3<S>,
+
+  /// How far `tex_coord` moves between neighbouring pixels, which is only known for hits of rays with differentials
+  pub footprint: TextureFootprint,
+
+  /// `point` and its partial derivatives with respect to the texture coordinates in object space, where solid textures
+  /// are evaluated so that they stay fixed to the surface as it moves
+  pub object_point: ObjectPoint,
+  pub object_dpdu: ObjectVector,
+  pub object_dpdv: ObjectVector
 }
 
 pub type WorldSurfacePoint = SurfacePoint<WorldSpace>;
 
+impl WorldSurfacePoint {
+  pub fn texture_point(&self) -> TexturePoint {
+    TexturePoint { tex_coord: self.tex_coord, object_point: self.object_point, footprint: self.footprint }
+  }
+
+  /// Where a ray meets the plane tangent to the surface at this point, unless it runs parallel to it.
+  fn tangent_plane_hit(&self, origin: &WorldPoint, dir: &WorldUnitVector) -> Option<WorldPoint> {
+    let n = self.geometric_normal.into_vector();
+    let dir = dir.into_vector();
+    let denom = n.dot(&dir);
+    if denom.abs() < 1e-8 {
+      return None;
+    }
+
+    Some(*origin + dir * (n.dot(&(self.point - *origin)) / denom))
+  }
+
+  /// Estimates the footprint of a pixel in texture space from where the differentials of the ray which hit this point
+  /// meet its tangent plane.
+  pub fn estimate_footprint(&self, differentials: &WorldRayDifferentials) -> TextureFootprint {
+    let (Some(x_hit), Some(y_hit)) = (
+      self.tangent_plane_hit(&differentials.x_origin, &differentials.x_dir),
+      self.tangent_plane_hit(&differentials.y_origin, &differentials.y_dir)
+    ) else {
+      return TextureFootprint::point();
+    };
+
+    // dp/dx = dp/du du/dx + dp/dv dv/dx is overdetermined, so solve it in the two axes the surface is least edge-on to
+    let n = self.geometric_normal.into_vector();
+    let n = [n[0].abs(), n[1].abs(), n[2].abs()];
+    let (a, b) = if n[0] > n[1] && n[0] > n[2] {
+      (1, 2)
+    } else if n[1] > n[2] {
+      (0, 2)
+    } else {
+      (0, 1)
+    };
+
+    let (dpdu, dpdv) = (self.dpdu, self.dpdv);
+    let det = dpdu[a] * dpdv[b] - dpdv[a] * dpdu[b];
+    if det.abs() < 1e-12 {
+      return TextureFootprint::point();
+    }
+
+    let solve = |dp: Vector3<WorldSpace>| {
+      TextureCoordinate::from_array([
+        (dpdv[b] * dp[a] - dpdv[a] * dp[b]) / det,
+        (dpdu[a] * dp[b] - dpdu[b] * dp[a]) / det
+      ])
+    };
+
+    TextureFootprint { duv_dx: solve(x_hit - self.point), duv_dy: solve(y_hit - self.point) }
+  }
+
+  /// Follows the differentials of a ray arriving from `out_dir` through a specular bounce into `in_dir`, treating the
+  /// surface as flat across the pixel. Refracted differentials are bent as much as the ray itself was, so at normal
+  /// incidence (where that can't be told) they pass straight through.
+  pub fn scatter_differentials(
+    &self,
+    differentials: &WorldRayDifferentials,
+    out_dir: &WorldUnitVector,
+    in_dir: &WorldUnitVector
+  ) -> Option<WorldRayDifferentials> {
+    let n = self.shading_normal.into_vector();
+    let (in_dir, out_dir) = (in_dir.into_vector(), out_dir.into_vector());
+    let tangential = |v: Vector3<WorldSpace>| v - n * n.dot(&v);
+
+    let ng = self.geometric_normal.into_vector();
+    let is_reflection = in_dir.dot(&ng) * out_dir.dot(&ng) > 0.0;
+    let incident_tangential = tangential(out_dir).norm();
+    let ratio = if incident_tangential > 1e-4 { tangential(in_dir).norm() / incident_tangential } else { 1.0 };
+    let side = in_dir.dot(&n).signum();
+
+    let scatter = |dir: &WorldUnitVector| {
+      let dir = dir.into_vector();
+      if is_reflection {
+        (dir - n * (2.0 * n.dot(&dir))).normalize()
+      } else {
+        let t = tangential(dir) * ratio;
+        (t + n * (side * (1.0 - t.norm_squared()).max(0.0).sqrt())).normalize()
+      }
+    };
+
+    Some(RayDifferentials {
+      x_origin: self.tangent_plane_hit(&differentials.x_origin, &differentials.x_dir)?,
+      x_dir: scatter(&differentials.x_dir),
+      y_origin: self.tangent_plane_hit(&differentials.y_origin, &differentials.y_dir)?,
+      y_dir: scatter(&differentials.y_dir)
+    })
+  }
+}
+
 #[derive(Debug, Clone)]
 pub struct SurfaceInterface<'a, S: Space<3>> {
   pub surface_point: SurfacePoint<S>,
   pub light: &'a dyn Light,
   pub material: &'a dyn Material,
+  pub visibility: &'a Visibility,
   pub intersect_dist: PositiveReal // TODO: Get this outta here
 }
 
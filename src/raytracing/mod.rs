@@ -1,7 +1,9 @@
 mod intersection;
 mod ray;
+mod shading_frame;
 mod termination;
 
 pub use intersection::*;
 pub use ray::*;
+pub use shading_frame::*;
 pub use termination::*;
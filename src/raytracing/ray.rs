@@ -4,13 +4,39 @@ use nalgebra::{Const, ToTypenum};
 
 use crate::math::*;
 
+/// What a ray is looking for, which decides the surfaces it can see.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RayKind {
+  Camera,
+
+  /// Any ray scattered from a surface or medium
+  Reflection,
+
+  /// A ray testing whether light reaches a point
+  Shadow
+}
+
+/// Rays offset from a camera ray by a pixel in x and in y, which follow it through specular bounces to track how much
+/// of the scene a pixel covers.
+#[derive(Debug, Clone, Copy)]
+pub struct RayDifferentials<const D: usize, S: Space<D>>
+where Const<D>: ToTypenum
+{
+  pub x_origin: Point<D, S>,
+  pub x_dir: UnitVector<D, S>,
+  pub y_origin: Point<D, S>,
+  pub y_dir: UnitVector<D, S>
+}
+
 #[derive(Debug, Clone)]
 pub struct Ray<const D: usize, S: Space<D>>
 where Const<D>: ToTypenum
 {
   max_intersect_time: PositiveReal,
   origin: Point<D, S>,
-  dir: UnitVector<D, S>
+  dir: UnitVector<D, S>,
+  kind: RayKind,
+  differentials: Option<RayDifferentials<D, S>>
 }
 
 const MIN_INTERSECT_TIME: PositiveReal = PositiveReal::new_unchecked(0.001);
@@ -19,11 +45,28 @@ impl<const D: usize, S: Space<D>> Ray<D, S>
 where Const<D>: ToTypenum
 {
   pub fn new(origin: Point<D, S>, dir: UnitVector<D, S>) -> Self {
-    Self { max_intersect_time: PositiveReal::MAX, origin, dir }
+    Self { max_intersect_time: PositiveReal::MAX, origin, dir, kind: RayKind::Reflection, differentials: None }
   }
 
   pub fn new_with_time(max_time: PositiveReal, origin: Point<D, S>, dir: UnitVector<D, S>) -> Self {
-    Self { max_intersect_time: max_time, origin, dir }
+    Self { max_intersect_time: max_time, origin, dir, kind: RayKind::Reflection, differentials: None }
+  }
+
+  pub fn with_kind(self, kind: RayKind) -> Self { Self { kind, ..self } }
+
+  pub fn with_differentials(self, differentials: Option<RayDifferentials<D, S>>) -> Self {
+    Self { differentials, ..self }
+  }
+
+  /// Moves the differentials towards the ray by `scale`, for when each pixel is covered by many rays.
+  pub fn scale_differentials(&mut self, scale: Real) {
+    let (origin, dir) = (self.origin, self.dir.into_vector());
+    if let Some(d) = &mut self.differentials {
+      d.x_origin = origin + (d.x_origin - origin) * scale;
+      d.x_dir = (dir + (d.x_dir.into_vector() - dir) * scale).normalize();
+      d.y_origin = origin + (d.y_origin - origin) * scale;
+      d.y_dir = (dir + (d.y_dir.into_vector() - dir) * scale).normalize();
+    }
   }
 
   fn at_unchecked(&self, t: PositiveReal) -> Point<D, S> { self.origin + self.dir * t.into_inner() }
@@ -50,6 +93,10 @@ where Const<D>: ToTypenum
 
   pub fn dir(&self) -> UnitVector<D, S> { self.dir }
 
+  pub fn kind(&self) -> RayKind { self.kind }
+
+  pub fn differentials(&self) -> Option<&RayDifferentials<D, S>> { self.differentials.as_ref() }
+
   pub fn min_intersect_time(&self) -> PositiveReal { MIN_INTERSECT_TIME }
 
   pub fn max_intersect_time(&self) -> PositiveReal { self.max_intersect_time }
@@ -70,3 +117,5 @@ where Const<D>: ToTypenum
 pub type Ray3<S> = Ray<3, S>;
 
 pub type WorldRay = Ray3<WorldSpace>;
+
+pub type WorldRayDifferentials = RayDifferentials<3, WorldSpace>;
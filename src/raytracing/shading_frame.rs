@@ -0,0 +1,56 @@
+use super::*;
+use crate::math::*;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ShadingSpace;
+
+impl Space<3> for ShadingSpace {}
+
+/// A right-handed orthonormal basis at a surface point whose z-axis is the shading normal and whose x-axis follows
+/// the direction of increasing u, which lets BSDFs work in a canonical local space.
+#[derive(Debug, Clone, Copy)]
+pub struct ShadingFrame {
+  pub tangent: WorldUnitVector,
+  pub bitangent: WorldUnitVector,
+  pub normal: WorldUnitVector
+}
+
+impl ShadingFrame {
+  pub fn to_local(self, v: &WorldUnitVector) -> UnitVector3<ShadingSpace> {
+    UnitVector3::from_array([v.dot(&self.tangent), v.dot(&self.bitangent), v.dot(&self.normal)])
+  }
+
+  pub fn to_world(self, v: &UnitVector3<ShadingSpace>) -> WorldUnitVector {
+    let v = v.into_vector();
+    (self.tangent * v[0] + self.bitangent * v[1] + self.normal * v[2]).normalize()
+  }
+
+  /// Rotates the tangent and bitangent counterclockwise about the normal by `angle` radians.
+  pub fn rotated(&self, angle: Real) -> Self {
+    let (sin, cos) = angle.sin_cos();
+    Self {
+      tangent: (self.tangent * cos + self.bitangent * sin).normalize(),
+      bitangent: (self.bitangent * cos - self.tangent * sin).normalize(),
+      normal: self.normal
+    }
+  }
+
+  /// Turns the frame upside down while keeping it right-handed.
+  pub fn flipped(&self) -> Self { Self { tangent: self.tangent, bitangent: -self.bitangent, normal: -self.normal } }
+}
+
+impl WorldSurfacePoint {
+  /// Builds the shading frame by making the shading tangent orthogonal to the (possibly perturbed) shading normal.
+  pub fn shading_frame(&self) -> ShadingFrame {
+    let n = self.shading_normal;
+    let projected = self.shading_tangent.into_vector() - n * n.dot(&self.shading_tangent);
+    let (tangent, bitangent) = if projected.norm_squared() > 1e-12 {
+      let tangent = projected.normalize();
+      (tangent, n.into_vector().cross(&tangent.into_vector()).normalize())
+    } else {
+      n.orthonormal_basis()
+    };
+
+    ShadingFrame { tangent, bitangent, normal: n }
+  }
+}
@@ -1,6 +1,7 @@
 use std::{
+  collections::HashMap,
   error::Error,
-  sync::{Arc, Mutex},
+  sync::{Arc, LazyLock, Mutex},
   thread,
   time::Duration
 };
@@ -11,16 +12,17 @@ use serde::Deserialize;
 use threadpool::{Builder, ThreadPool};
 
 use crate::{
+  background::BackgroundParameters,
   camera::*,
   duration_to_hms,
   integrators::*,
-  lights::LightParameters,
+  lights::{LightParameters, LightSourceParameters},
   materials::MaterialParameters,
   math::*,
   sampling::*,
   scene::Scene,
   spectrum::*,
-  surfaces::{self, MeshParameters, SurfaceParameters},
+  surfaces::{self, LightSelection, MeshParameters, SurfaceParameters},
   BuildSettings, RenderSettings
 };
 
@@ -35,6 +37,12 @@ pub struct SceneParameters {
   #[serde(alias = "lights", default)]
   pub light_params: Vec<Box<dyn LightParameters>>,
 
+  #[serde(alias = "light-sources", default)]
+  pub light_source_params: Vec<Box<dyn LightSourceParameters>>,
+
+  #[serde(alias = "background", default)]
+  pub background_params: Option<BackgroundParameters>,
+
   #[serde(alias = "materials", default)]
   pub material_params: Vec<Box<dyn MaterialParameters>>,
 
@@ -44,12 +52,29 @@ pub struct SceneParameters {
   #[serde(alias = "surfaces", default)]
   pub surface_params: Vec<Box<dyn SurfaceParameters>>,
 
+  #[serde(alias = "light-selection", default)]
+  pub light_selection: LightSelection,
+
   #[serde(alias = "integrator", default = "crate::integrators::default_integrator")]
-  pub integrator_params: Box<dyn IntegratorParameters>
+  pub integrator_params: Box<dyn IntegratorParameters>,
+
+  /// Whether paths carry a few sampled wavelengths rather than RGB, with colors upsampled to smooth spectra
+  #[serde(default)]
+  pub spectral: bool,
+
+  /// The color space colors in the scene are given in and light is computed in
+  #[serde(alias = "working-space", default)]
+  pub working_space: ColorSpace,
+
+  /// The color space the rendered image is written in
+  #[serde(alias = "display-space", default)]
+  pub display_space: DisplaySpace
 }
 
 pub struct Renderer {
   samples_per_pixel: usize,
+  spectral: bool,
+  display_space: DisplaySpace,
   camera: Arc<Camera>,
   integrator: Arc<Box<dyn Integrator>>
 }
@@ -62,15 +87,29 @@ impl Renderer {
       samples_per_pixel,
       camera_params,
       light_params,
+      light_source_params,
+      background_params,
       material_params,
       mesh_params,
       surface_params,
-      integrator_params
+      light_selection,
+      integrator_params,
+      spectral,
+      working_space,
+      display_space
     } = params;
 
-    // Build lights and materials
+    // Every color is converted into the working space as it is built
+    set_working_space(working_space);
+    let settings = BuildSettings { light_selection, ..settings };
+
+    // Build lights and materials, where materials may refer to any material declared before them
     let lights = light_params.into_iter().map(|p| (p.name(), p.build_light())).collect();
-    let materials = material_params.into_iter().map(|p| (p.name(), p.build_material())).collect();
+    let mut materials = HashMap::new();
+    for p in material_params {
+      let material = p.build_material(&materials);
+      materials.insert(p.name(), material);
+    }
 
     // Load meshes from files
     let meshes = mesh_params.into_iter().map(|p| p.build_mesh()).collect();
@@ -80,16 +119,29 @@ impl Renderer {
       surface_params.into_iter().partition(|s| s.has_light());
     let non_emissive_surface =
       surfaces::default_grouping(non_emissive_surface_params, &lights, &materials, &meshes, settings);
-    let emissive_surface = surfaces::default_grouping(emissive_surface_params, &lights, &materials, &meshes, settings);
+    let emissive_surface = surfaces::emissive_grouping(emissive_surface_params, &lights, &materials, &meshes, settings);
 
-    // Build the scene from the surface partition
-    let scene = Scene::new(non_emissive_surface, emissive_surface);
+    // Build the scene from the surface partition and the lights which exist independently of surfaces
+    let light_sources = light_source_params.iter().map(|p| (p.name(), p.build_light_source())).collect();
+    let background = background_params.map(|p| p.build_background()).unwrap_or_default();
+    let scene = Scene::new(non_emissive_surface, emissive_surface, light_sources, background);
 
     // Build integrator from scene
     let integrator = integrator_params.build_integrator(scene, settings)?;
 
+    // Fit the spectra for colors up front rather than in the middle of rendering
+    if spectral {
+      LazyLock::force(&RGB_TO_SPECTRUM);
+    }
+
     // Return the scene with its camera
-    Ok(Self { samples_per_pixel, camera: Arc::new(camera_params.build_camera()), integrator: Arc::new(integrator) })
+    Ok(Self {
+      samples_per_pixel,
+      spectral,
+      display_space,
+      camera: Arc::new(camera_params.build_camera()),
+      integrator: Arc::new(integrator)
+    })
   }
 
   pub fn render(&self, settings: RenderSettings) -> DynamicImage {
@@ -145,14 +197,7 @@ impl Renderer {
     // Send jobs to the threadpool.
     for subimage_window in subimage_windows {
       // Send the render job to the threadpool
-      Self::async_integrate_subimage(
-        &thread_pool,
-        &self.integrator,
-        &self.camera,
-        self.samples_per_pixel,
-        &image_lock,
-        subimage_window
-      );
+      self.async_integrate_subimage(&thread_pool, &image_lock, subimage_window);
     }
 
     // Manually update the progress bar as the threads run.
@@ -188,16 +233,15 @@ impl Renderer {
   }
 
   fn async_integrate_subimage(
+    &self,
     thread_pool: &ThreadPool,
-    integrator: &Arc<Box<dyn Integrator>>,
-    camera: &Arc<Camera>,
-    samples_per_pixel: usize,
     image_lock: &Arc<Mutex<Image>>,
     ((sub_x, sub_y), (sub_w, sub_h)): ((u32, u32), (u32, u32))
   ) {
-    // Copy the ARCs.
-    let integrator = integrator.clone();
-    let camera = camera.clone();
+    // Copy the ARCs and settings.
+    let integrator = self.integrator.clone();
+    let camera = self.camera.clone();
+    let (samples_per_pixel, spectral, display_space) = (self.samples_per_pixel, self.spectral, self.display_space);
     let image_lock = image_lock.clone();
 
     // Send the render job to the thread pool.
@@ -208,6 +252,10 @@ impl Renderer {
       // Precompute divisions to save some time.
       let inv_num_samples = 1.0 / (samples_per_pixel as Real);
 
+      // Each sample only needs to cover its share of the pixel, though shrinking the texture lookups too far brings
+      // back the aliasing that filtering removes.
+      let differential_scale = (1.0 / (samples_per_pixel as Real).sqrt()).max(0.125);
+
       // Build samplers for this subimage thread.
       let mut ray_sampler = IndependentSampler::new();
       let mut integrator_sampler = IndependentSampler::new();
@@ -217,28 +265,35 @@ impl Renderer {
       for x in 0..sub_w {
         for y in 0..sub_h {
           let mut light = Spectrum::none();
+          let mut xyz = [0.0; 3];
           for _ in 0..samples_per_pixel {
             // Generate a slightly jittered ray through pixel (x, y).
             let ray_x = ray_sampler.next() + (sub_x + x) as Real;
             let ray_y = ray_sampler.next() + (sub_y + y) as Real;
-            let ray = camera.sample_ray_through_pixel(&mut ray_sampler, ray_x, ray_y);
-
-            // Add the incoming radiance to our running average.
-            light += integrator.radiance_estimate(&mut integrator_sampler, ray);
-          }
-
-          // Convert to sRGB, which is the color space expected by the image buffer
-          let mut srgb = light * inv_num_samples;
-          for c in srgb.inner.iter_mut() {
-            if *c <= 0.0031308 {
-              *c *= 12.92;
+            let mut ray = camera.sample_ray_through_pixel(&mut ray_sampler, ray_x, ray_y);
+            ray.scale_differentials(differential_scale);
+
+            // Add the incoming radiance to our running average, which in spectral mode is kept in CIE XYZ.
+            if spectral {
+              let mut wavelengths = SampledWavelengths::sample(integrator_sampler.next().into_inner());
+              let radiance = integrator.spectral_radiance_estimate(&mut integrator_sampler, ray, &mut wavelengths);
+              for (sum, c) in xyz.iter_mut().zip(wavelengths.to_xyz(&radiance)) {
+                *sum += c;
+              }
             } else {
-              *c = (1.0 + 0.055) * (*c).powf(1.0 / 2.4) - 0.055;
+              light += integrator.radiance_estimate(&mut integrator_sampler, ray);
             }
           }
 
-          // Convert the sRGB pixel value into bytes and write to the temporary buffer.
-          let bytes = srgb.bytes();
+          if spectral {
+            light = xyz_to_spectrum(xyz);
+          }
+
+          // Convert to the display space, which is what the image is written in
+          let encoded = display_space.encode(&(light * inv_num_samples));
+
+          // Convert the encoded pixel value into bytes and write to the temporary buffer.
+          let bytes = encoded.bytes();
           subimage.put_pixel(x, y, image::Rgb([bytes[0], bytes[1], bytes[2]]));
         }
       }
@@ -10,6 +10,10 @@ pub struct IndependentSampler {
 
 impl IndependentSampler {
   pub fn new() -> Self { Self { rng: StdRng::from_seed(rand::thread_rng().gen()) } }
+
+  /// A sampler which draws the same sequence every time, so that tests are reproducible.
+  #[cfg(test)]
+  pub fn seeded(seed: u64) -> Self { Self { rng: StdRng::seed_from_u64(seed) } }
 }
 
 impl Sampler for IndependentSampler {
@@ -1,7 +1,9 @@
 mod independent;
+mod piecewise_constant;
 mod random_variable;
 mod sampler;
 
 pub use independent::*;
+pub use piecewise_constant::*;
 pub use random_variable::*;
 pub use sampler::*;
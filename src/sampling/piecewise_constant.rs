@@ -0,0 +1,99 @@
+use super::*;
+use crate::math::*;
+
+/// A distribution over [0, 1) whose density is proportional to a non-negative step function with equally wide steps.
+#[derive(Debug, Clone)]
+pub struct PiecewiseConstant1D {
+  func: Vec<Real>,
+  cdf: Vec<Real>,
+  integral: Real
+}
+
+impl PiecewiseConstant1D {
+  pub fn new(func: Vec<Real>) -> Self {
+    let n = func.len() as Real;
+    let mut cdf = Vec::with_capacity(func.len() + 1);
+    cdf.push(0.0);
+    for f in &func {
+      cdf.push(cdf.last().unwrap() + f.max(0.0) / n);
+    }
+
+    // A function which is zero everywhere is treated as constant instead
+    let integral = *cdf.last().unwrap();
+    if integral > 0.0 {
+      cdf.iter_mut().for_each(|c| *c /= integral);
+    } else {
+      cdf.iter_mut().enumerate().for_each(|(i, c)| *c = i as Real / n);
+    }
+
+    Self { func, cdf, integral }
+  }
+
+  /// The integral of the step function over [0, 1).
+  pub fn integral(&self) -> Real { self.integral }
+
+  /// Maps a uniform random number to a sample, returning it along with its density and the index of its step.
+  pub fn sample_from_uniform(&self, u: Real) -> Option<(Real, PositiveReal, usize)> {
+    let index = self.cdf.partition_point(|c| *c <= u).clamp(1, self.func.len()) - 1;
+    let width = self.cdf[index + 1] - self.cdf[index];
+    let offset = if width > 0.0 { (u - self.cdf[index]) / width } else { 0.0 };
+    let x = (index as Real + offset.clamp(0.0, 1.0)) / self.func.len() as Real;
+    self.pdf_of_index(index).map(|pdf| (x.min(1.0 - Real::EPSILON), pdf, index))
+  }
+
+  pub fn pdf_of_index(&self, index: usize) -> Option<PositiveReal> {
+    if self.integral > 0.0 {
+      PositiveReal::new(self.func[index].max(0.0) / self.integral)
+    } else {
+      Some(PositiveReal::ONE)
+    }
+  }
+
+  pub fn index_of(&self, x: Real) -> usize { ((x * self.func.len() as Real) as usize).min(self.func.len() - 1) }
+}
+
+impl ContinuousRandomVariable for PiecewiseConstant1D {
+  type Param = ();
+  type Sample = Real;
+
+  fn sample_with_pdf(&self, _: &Self::Param, sampler: &mut dyn Sampler) -> Option<(Self::Sample, PositiveReal)> {
+    self.sample_from_uniform(sampler.next().into_inner()).map(|(x, pdf, _)| (x, pdf))
+  }
+
+  fn pdf(&self, _: &Self::Param, x: &Self::Sample) -> Option<PositiveReal> { self.pdf_of_index(self.index_of(*x)) }
+}
+
+/// A distribution over [0, 1)^2 whose density is proportional to a non-negative function which is constant on each
+/// cell of a grid, sampled by picking a row from the marginal distribution and then a column within that row.
+#[derive(Debug, Clone)]
+pub struct PiecewiseConstant2D {
+  rows: Vec<PiecewiseConstant1D>,
+  marginal: PiecewiseConstant1D
+}
+
+impl PiecewiseConstant2D {
+  /// Builds the distribution from a row-major grid of `width` columns.
+  pub fn new(func: &[Real], width: usize) -> Self {
+    let rows: Vec<_> = func.chunks_exact(width).map(|row| PiecewiseConstant1D::new(row.to_vec())).collect();
+    let marginal = PiecewiseConstant1D::new(rows.iter().map(|r| r.integral()).collect());
+    Self { rows, marginal }
+  }
+}
+
+impl ContinuousRandomVariable for PiecewiseConstant2D {
+  type Param = ();
+  /// A point whose coordinates are the horizontal and vertical positions within the grid respectively
+  type Sample = (Real, Real);
+
+  fn sample_with_pdf(&self, _: &Self::Param, sampler: &mut dyn Sampler) -> Option<(Self::Sample, PositiveReal)> {
+    let (y, marginal_pdf, row) = self.marginal.sample_from_uniform(sampler.next().into_inner())?;
+    let (x, conditional_pdf, _) = self.rows[row].sample_from_uniform(sampler.next().into_inner())?;
+    Some(((x, y), marginal_pdf * conditional_pdf))
+  }
+
+  fn pdf(&self, _: &Self::Param, &(x, y): &Self::Sample) -> Option<PositiveReal> {
+    let row = self.marginal.index_of(y);
+    let conditional_pdf = self.rows[row].pdf_of_index(self.rows[row].index_of(x))?;
+    self.marginal.pdf_of_index(row).map(|marginal_pdf| marginal_pdf * conditional_pdf)
+  }
+}
@@ -1,13 +1,7 @@
 use std::fmt::Debug;
 
 use super::Sampler;
-use crate::math::PositiveReal;
-
-#[derive(Debug)]
-pub enum RandomVariable<P, S> {
-  Diffuse(Box<dyn ContinuousRandomVariable<Param = P, Sample = S>>),
-  Specular(Box<dyn DiscreteRandomVariable<Param = P, Sample = S>>)
-}
+use crate::math::{PositiveReal, Real};
 
 pub trait ContinuousRandomVariable: Debug {
   type Param;
@@ -28,3 +22,9 @@ pub trait DiscreteRandomVariable: Debug {
 
   fn sample(&self, param: &Self::Param, sampler: &mut dyn Sampler) -> Option<Self::Sample>;
 }
+
+/// Converts a pdf with respect to surface area into one with respect to solid angle, as seen from a point `dist` away
+/// in a direction whose cosine with the surface normal is `cos`.
+pub fn area_to_solid_angle_pdf(area_pdf: Real, dist: Real, cos: Real) -> Option<PositiveReal> {
+  PositiveReal::new(area_pdf * dist * dist / cos.abs()).filter(|pdf| pdf.into_inner().is_finite())
+}
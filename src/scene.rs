@@ -1,16 +1,29 @@
-use crate::{raytracing::*, surfaces::Surface};
+use crate::{
+  background::Background, lights::LightSource, math::*, raytracing::*, spectrum::Spectrum, surfaces::Surface
+};
 
 const NUM_PARTS: usize = 2;
 
 pub struct Scene {
   /// The element surface_partition[0] is the non-emissive part of the scene, and likewise
   /// surface_partition[1] is the emissive part of the scene
-  surface_partition: [Box<dyn Surface>; NUM_PARTS]
+  surface_partition: [Box<dyn Surface>; NUM_PARTS],
+
+  /// Lights which aren't attached to any surface, along with their names
+  light_sources: Vec<Box<dyn LightSource>>,
+  light_source_names: Vec<Option<String>>,
+  background: Background
 }
 
 impl Scene {
-  pub fn new(non_emissive_part: Box<dyn Surface>, emissive_part: Box<dyn Surface>) -> Self {
-    Self { surface_partition: [non_emissive_part, emissive_part] }
+  pub fn new(
+    non_emissive_part: Box<dyn Surface>,
+    emissive_part: Box<dyn Surface>,
+    light_sources: Vec<(Option<String>, Box<dyn LightSource>)>,
+    background: Background
+  ) -> Self {
+    let (light_source_names, light_sources) = light_sources.into_iter().unzip();
+    Self { surface_partition: [non_emissive_part, emissive_part], light_sources, light_source_names, background }
   }
 
   pub fn intersect_world_ray(&self, mut ray: WorldRay) -> Option<WorldSurfaceInterface> {
@@ -22,10 +35,38 @@ impl Scene {
       }
     }
 
+    if let (Some(hit), Some(differentials)) = (&mut closest, ray.differentials()) {
+      hit.surface_point.footprint = hit.surface_point.estimate_footprint(differentials);
+    }
+
     closest
   }
 
+  /// Whether nothing blocks the path from `point` in direction `dir` for a distance of `dist` (or forever, if `None`).
+  pub fn unoccluded(&self, point: WorldPoint, dir: WorldUnitVector, dist: Option<PositiveReal>) -> bool {
+    let ray = match dist {
+      Some(dist) => Ray::new_with_time(dist, point, dir),
+      None => Ray::new(point, dir)
+    }
+    .with_kind(RayKind::Shadow);
+
+    self.intersect_world_ray(ray).is_none()
+  }
+
   pub fn emissive_part(&self) -> &dyn Surface { self.surface_partition[1].as_ref() }
+
+  pub fn light_sources(&self) -> &[Box<dyn LightSource>] { &self.light_sources }
+
+  pub fn light_source_name(&self, index: usize) -> Option<&str> { self.light_source_names[index].as_deref() }
+
+  pub fn background(&self) -> &Background { &self.background }
+
+  /// The radiance arriving along a ray which escapes the scene in direction `dir`, from both the background and any
+  /// light sources.
+  pub fn radiance_escaped(&self, dir: &WorldUnitVector) -> Spectrum {
+    let background = self.background.radiance(dir);
+    self.light_sources.iter().fold(background, |radiance, l| radiance + l.radiance_escaped(dir))
+  }
 }
 
 unsafe impl Send for Scene {}
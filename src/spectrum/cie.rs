@@ -0,0 +1,133 @@
+use serde::Deserialize;
+
+use super::{working_space, Spectrum};
+use crate::math::Real;
+
+/// A piecewise Gaussian, with a different width on either side of its peak.
+fn lobe(wavelength: f64, peak: f64, width_below: f64, width_above: f64) -> f64 {
+  let t = (wavelength - peak) / if wavelength < peak { width_below } else { width_above };
+  (-0.5 * t * t).exp()
+}
+
+/// The CIE 1931 2° color matching functions at a wavelength in nanometers, using the multi-lobe fit of Wyman, Sloan
+/// and Shirley.
+pub fn color_matching(wavelength: f64) -> [f64; 3] {
+  let x = 1.056 * lobe(wavelength, 599.8, 37.9, 31.0) + 0.362 * lobe(wavelength, 442.0, 16.0, 26.7)
+    - 0.065 * lobe(wavelength, 501.1, 20.4, 26.2);
+  let y = 0.821 * lobe(wavelength, 568.8, 46.9, 40.5) + 0.286 * lobe(wavelength, 530.9, 16.3, 31.1);
+  let z = 1.217 * lobe(wavelength, 437.0, 11.8, 36.0) + 0.681 * lobe(wavelength, 459.0, 26.0, 13.8);
+  [x, y, z]
+}
+
+/// The range of wavelengths in nanometers over which the color matching functions are integrated.
+pub const VISIBLE_WAVELENGTHS: (f64, f64) = (360.0, 830.0);
+
+/// The matrices converting between CIE XYZ and linear sRGB.
+pub const XYZ_TO_SRGB: [[f64; 3]; 3] =
+  [[3.2404542, -1.5371385, -0.4985314], [-0.9692660, 1.8760108, 0.0415560], [0.0556434, -0.2040259, 1.0572252]];
+pub const SRGB_TO_XYZ: [[f64; 3]; 3] =
+  [[0.4124564, 0.3575761, 0.1804375], [0.2126729, 0.7151522, 0.0721750], [0.0193339, 0.1191920, 0.9503041]];
+
+/// Multiplies a 3x3 matrix by a vector.
+pub fn transform_color(matrix: &[[f64; 3]; 3], color: [f64; 3]) -> [f64; 3] {
+  matrix.map(|row| row[0] * color[0] + row[1] * color[1] + row[2] * color[2])
+}
+
+/// Converts CIE XYZ to the working color space, clamping colors outside of its gamut.
+pub fn xyz_to_spectrum(xyz: [f64; 3]) -> Spectrum {
+  let [r, g, b] = transform_color(working_space().xyz_to_rgb(), xyz).map(|c| c.max(0.0) as Real);
+  Spectrum::new(r, g, b)
+}
+
+/// The relative spectral power of CIE standard illuminant D65 at every 10nm over the visible wavelengths.
+const D65: [f64; 48] = [
+  46.6383, 52.0891, 49.9755, 54.6482, 82.7549, 91.4860, 93.4318, 86.6823, 104.865, 117.008, 117.812, 114.861, 115.923,
+  108.811, 109.354, 107.802, 104.790, 107.689, 104.405, 104.046, 100.000, 96.3342, 95.7880, 88.6856, 90.0062, 89.5991,
+  87.6987, 83.2886, 83.6992, 80.0268, 80.2146, 82.2778, 78.2842, 69.7213, 71.6091, 74.3490, 61.6040, 69.8856, 75.0870,
+  63.5927, 46.4182, 66.8054, 63.3828, 64.3040, 59.4519, 51.9590, 57.4406, 60.3125
+];
+
+/// The relative spectral power of illuminant D65 at a wavelength in nanometers, interpolated from the CIE's table.
+pub fn d65(wavelength: f64) -> f64 {
+  let x = ((wavelength - VISIBLE_WAVELENGTHS.0) / 10.0).clamp(0.0, (D65.len() - 1) as f64);
+  let i = (x as usize).min(D65.len() - 2);
+  let t = x - i as f64;
+  D65[i] * (1.0 - t) + D65[i + 1] * t
+}
+
+/// The color with chromaticity `(x, y)` and a luminance of one.
+pub fn chromaticity_to_spectrum(x: f64, y: f64) -> Spectrum { xyz_to_spectrum([x / y, 1.0, (1.0 - x - y) / y]) }
+
+/// The coolest blackbody whose color is accepted, since far cooler ones emit so little visible light that it underflows
+/// to zero.
+pub const MIN_BLACKBODY_TEMPERATURE: Real = 500.0;
+
+/// The color of an ideal blackbody at a temperature in Kelvin, following Planck's law, with a luminance of one.
+pub fn blackbody(temperature: Real) -> Spectrum {
+  // Constant factors of Planck's law cancel out in the normalization, leaving only the second radiation constant
+  const C2: f64 = 1.4387769e7; // nm K
+  let (min, max) = VISIBLE_WAVELENGTHS;
+  let mut xyz = [0.0; 3];
+  for step in 0..=(max - min) as usize {
+    let wavelength = min + step as f64;
+    let radiance = 1.0 / (wavelength.powi(5) * ((C2 / (wavelength * temperature as f64)).exp() - 1.0));
+    for (sum, weight) in xyz.iter_mut().zip(color_matching(wavelength)) {
+      *sum += radiance * weight;
+    }
+  }
+
+  xyz_to_spectrum(xyz.map(|c| c / xyz[1]))
+}
+
+/// The CIE standard illuminants: incandescent light (A), daylight (D series) and fluorescent lamps (F series).
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub enum StandardIlluminant {
+  A,
+  D50,
+  D55,
+  D65,
+  D75,
+  F1,
+  F2,
+  F3,
+  F4,
+  F5,
+  F6,
+  F7,
+  F8,
+  F9,
+  F10,
+  F11,
+  F12
+}
+
+impl StandardIlluminant {
+  /// The chromaticity of the illuminant for the CIE 1931 2° observer.
+  pub fn chromaticity(self) -> (f64, f64) {
+    match self {
+      StandardIlluminant::A => (0.44757, 0.40745),
+      StandardIlluminant::D50 => (0.34567, 0.35850),
+      StandardIlluminant::D55 => (0.33242, 0.34743),
+      StandardIlluminant::D65 => (0.31271, 0.32902),
+      StandardIlluminant::D75 => (0.29902, 0.31485),
+      StandardIlluminant::F1 => (0.31310, 0.33710),
+      StandardIlluminant::F2 => (0.37210, 0.37510),
+      StandardIlluminant::F3 => (0.40910, 0.39410),
+      StandardIlluminant::F4 => (0.44020, 0.40310),
+      StandardIlluminant::F5 => (0.31380, 0.34520),
+      StandardIlluminant::F6 => (0.37790, 0.38820),
+      StandardIlluminant::F7 => (0.31290, 0.32920),
+      StandardIlluminant::F8 => (0.34580, 0.35860),
+      StandardIlluminant::F9 => (0.37410, 0.37270),
+      StandardIlluminant::F10 => (0.34580, 0.35880),
+      StandardIlluminant::F11 => (0.38050, 0.37690),
+      StandardIlluminant::F12 => (0.43700, 0.40420)
+    }
+  }
+
+  /// The color of the illuminant with a luminance of one.
+  pub fn color(self) -> Spectrum {
+    let (x, y) = self.chromaticity();
+    chromaticity_to_spectrum(x, y)
+  }
+}
@@ -2,20 +2,45 @@ use derive_more::{Add, AddAssign, Div, DivAssign, Mul, MulAssign};
 use nalgebra as na;
 use serde::Deserialize;
 
+use super::{blackbody, working_space, StandardIlluminant, MIN_BLACKBODY_TEMPERATURE};
 use crate::math::*;
 
+fn default_scale() -> Real { 1.0 }
+
+/// A color given either as linear sRGB, or as the color of a blackbody at some temperature in Kelvin or of a standard
+/// illuminant, which have a luminance of `scale`.
 #[derive(Clone, Copy, Debug, Deserialize)]
 #[serde(untagged)]
 pub enum ColorParameters {
   Array([Real; 3]),
-  Single(Real)
+  Single(Real),
+  Blackbody {
+    temperature: Real,
+
+    #[serde(default = "default_scale")]
+    scale: Real
+  },
+  Illuminant {
+    illuminant: StandardIlluminant,
+
+    #[serde(default = "default_scale")]
+    scale: Real
+  }
 }
 
 impl ColorParameters {
   pub fn build_color(self) -> Spectrum {
     match self {
       ColorParameters::Array([r, g, b]) => Spectrum::new(r, g, b),
-      ColorParameters::Single(c) => Spectrum::new(c, c, c)
+      ColorParameters::Single(c) => Spectrum::new(c, c, c),
+      ColorParameters::Blackbody { temperature, scale } => {
+        if temperature < MIN_BLACKBODY_TEMPERATURE {
+          panic!("Blackbody temperature must be at least {MIN_BLACKBODY_TEMPERATURE}K, but was {temperature}K!");
+        }
+
+        blackbody(temperature) * scale
+      },
+      ColorParameters::Illuminant { illuminant, scale } => illuminant.color() * scale
     }
   }
 }
@@ -52,7 +77,11 @@ impl Spectrum {
 
   pub fn b(&self) -> Real { self.inner.z }
 
-  pub fn luminance(&self) -> Real { self.inner.x * 0.212671 + self.inner.y * 0.715160 + self.inner.z * 0.072169 }
+  /// The CIE Y component of the color in the working space.
+  pub fn luminance(&self) -> Real {
+    let [r, g, b] = working_space().rgb_to_xyz()[1];
+    self.inner.x * r as Real + self.inner.y * g as Real + self.inner.z * b as Real
+  }
 
   pub fn bytes(&self) -> na::Vector3<u8> {
     let r = na::clamp(self.inner.x * 255.0, 0.0, 255.0);
@@ -0,0 +1,144 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use serde::Deserialize;
+
+use super::{transform_color, Spectrum, SRGB_TO_XYZ, XYZ_TO_SRGB};
+use crate::math::Real;
+
+/// Linear RGB color spaces, given by the matrices converting them to and from CIE XYZ. ACEScg's white point is adapted
+/// to D65 (with the Bradford transform) so that every space agrees on what white is.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[repr(u8)]
+pub enum ColorSpace {
+  #[default]
+  #[serde(alias = "linear-srgb")]
+  LinearSrgb,
+
+  #[serde(alias = "acescg")]
+  AcesCg,
+
+  #[serde(alias = "rec2020", alias = "rec-2020")]
+  Rec2020,
+
+  #[serde(alias = "display-p3")]
+  DisplayP3
+}
+
+const ACESCG_TO_XYZ: [[f64; 3]; 3] =
+  [[0.6522375, 0.1282361, 0.1699822], [0.2676722, 0.6743400, 0.0579878], [-0.0053818, 0.0013691, 1.0930705]];
+const XYZ_TO_ACESCG: [[f64; 3]; 3] =
+  [[1.6605853, -0.3152956, -0.2415093], [-0.6599261, 1.6083915, 0.0172986], [0.0090026, -0.0035669, 0.9136433]];
+const REC2020_TO_XYZ: [[f64; 3]; 3] =
+  [[0.6369580, 0.1446169, 0.1688810], [0.2627002, 0.6779981, 0.0593017], [0.0, 0.0280727, 1.0609851]];
+const XYZ_TO_REC2020: [[f64; 3]; 3] =
+  [[1.7166512, -0.3556708, -0.2533663], [-0.6666844, 1.6164812, 0.0157685], [0.0176399, -0.0427706, 0.9421031]];
+const DISPLAY_P3_TO_XYZ: [[f64; 3]; 3] =
+  [[0.4865709, 0.2656677, 0.1982173], [0.2289746, 0.6917385, 0.0792869], [0.0, 0.0451134, 1.0439444]];
+const XYZ_TO_DISPLAY_P3: [[f64; 3]; 3] =
+  [[2.4934969, -0.9313836, -0.4027108], [-0.8294890, 1.7626641, 0.0236247], [0.0358458, -0.0761724, 0.9568845]];
+
+impl ColorSpace {
+  /// Every color space, in declaration order, so that a space's discriminant is its index here.
+  const ALL: [ColorSpace; 4] = [ColorSpace::LinearSrgb, ColorSpace::AcesCg, ColorSpace::Rec2020, ColorSpace::DisplayP3];
+
+  pub fn rgb_to_xyz(self) -> &'static [[f64; 3]; 3] {
+    match self {
+      ColorSpace::LinearSrgb => &SRGB_TO_XYZ,
+      ColorSpace::AcesCg => &ACESCG_TO_XYZ,
+      ColorSpace::Rec2020 => &REC2020_TO_XYZ,
+      ColorSpace::DisplayP3 => &DISPLAY_P3_TO_XYZ
+    }
+  }
+
+  pub fn xyz_to_rgb(self) -> &'static [[f64; 3]; 3] {
+    match self {
+      ColorSpace::LinearSrgb => &XYZ_TO_SRGB,
+      ColorSpace::AcesCg => &XYZ_TO_ACESCG,
+      ColorSpace::Rec2020 => &XYZ_TO_REC2020,
+      ColorSpace::DisplayP3 => &XYZ_TO_DISPLAY_P3
+    }
+  }
+
+  /// Converts a color in this space into the space `to`, clamping colors outside of its gamut.
+  pub fn convert(self, color: &Spectrum, to: ColorSpace) -> Spectrum {
+    if self == to {
+      return *color;
+    }
+
+    let xyz = transform_color(self.rgb_to_xyz(), color.inner.map(|c| c as f64).into());
+    let [r, g, b] = transform_color(to.xyz_to_rgb(), xyz).map(|c| c.max(0.0) as Real);
+    Spectrum::new(r, g, b)
+  }
+}
+
+const _: () = {
+  let mut i = 0;
+  while i < ColorSpace::ALL.len() {
+    assert!(ColorSpace::ALL[i] as usize == i);
+    i += 1;
+  }
+};
+
+static WORKING_SPACE: AtomicU8 = AtomicU8::new(ColorSpace::LinearSrgb as u8);
+
+/// The color space the renderer works in, which colors are converted into as the scene is built. Colors don't carry
+/// their space with them, and materials compare their luminance as they are rendered, so this is shared by the whole
+/// renderer and set by `Renderer::build` for each scene.
+pub fn working_space() -> ColorSpace { ColorSpace::ALL[WORKING_SPACE.load(Ordering::Relaxed) as usize] }
+
+pub fn set_working_space(space: ColorSpace) { WORKING_SPACE.store(space as u8, Ordering::Relaxed) }
+
+/// Decodes a value encoded with the sRGB transfer function (which Display P3 shares).
+pub fn srgb_decode(c: Real) -> Real {
+  if c <= 0.04045 {
+    c / 12.92
+  } else {
+    ((c + 0.055) / 1.055).powf(2.4)
+  }
+}
+
+pub fn srgb_encode(c: Real) -> Real {
+  if c <= 0.0031308 {
+    c * 12.92
+  } else {
+    1.055 * c.powf(1.0 / 2.4) - 0.055
+  }
+}
+
+/// The color spaces images can be written in, which add a transfer function to the linear spaces.
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+pub enum DisplaySpace {
+  #[default]
+  #[serde(alias = "srgb")]
+  Srgb,
+
+  #[serde(alias = "display-p3")]
+  DisplayP3,
+
+  /// Rec. 2020 primaries, encoded for a display with the BT.1886 gamma of 2.4
+  #[serde(alias = "rec2020", alias = "rec-2020")]
+  Rec2020,
+
+  /// Linear sRGB, without a transfer function
+  #[serde(alias = "linear-srgb", alias = "linear")]
+  LinearSrgb
+}
+
+impl DisplaySpace {
+  /// Converts a color in the working space into this space, encoded with its transfer function.
+  pub fn encode(self, color: &Spectrum) -> Spectrum {
+    let (space, transfer): (ColorSpace, fn(Real) -> Real) = match self {
+      DisplaySpace::Srgb => (ColorSpace::LinearSrgb, srgb_encode),
+      DisplaySpace::DisplayP3 => (ColorSpace::DisplayP3, srgb_encode),
+      DisplaySpace::Rec2020 => (ColorSpace::Rec2020, |c| c.powf(1.0 / 2.4)),
+      DisplaySpace::LinearSrgb => (ColorSpace::LinearSrgb, |c| c)
+    };
+
+    let mut encoded = working_space().convert(color, space);
+    for c in encoded.inner.iter_mut() {
+      *c = transfer(*c);
+    }
+
+    encoded
+  }
+}
@@ -1,3 +1,11 @@
+mod cie;
 mod color;
+mod color_space;
+mod sampled;
+mod upsampling;
 
+pub use cie::*;
 pub use color::*;
+pub use color_space::*;
+pub use sampled::*;
+pub use upsampling::*;
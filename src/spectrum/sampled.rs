@@ -0,0 +1,188 @@
+use std::{
+  ops::{Add, AddAssign, Div, Mul, MulAssign},
+  sync::LazyLock
+};
+
+use derive_more::{Add, AddAssign, Div, DivAssign, Mul, MulAssign};
+use nalgebra as na;
+
+use super::{color_matching, d65, working_space, ColorSpace, Spectrum, RGB_TO_SPECTRUM, VISIBLE_WAVELENGTHS};
+use crate::math::Real;
+
+/// The number of wavelengths carried by each path in spectral mode.
+pub const NUM_WAVELENGTHS: usize = 4;
+
+/// The values a path carries for the light along it and the attenuation of that light, which are either RGB or the
+/// values of spectra at a few wavelengths.
+pub trait PathSpectrum:
+  Copy
+  + Add<Output = Self>
+  + AddAssign
+  + Mul<Output = Self>
+  + MulAssign
+  + Mul<Real, Output = Self>
+  + Div<Real, Output = Self>
+{
+  fn none() -> Self;
+
+  fn white() -> Self;
+}
+
+impl PathSpectrum for Spectrum {
+  fn none() -> Self { Spectrum::none() }
+
+  fn white() -> Self { Spectrum::white() }
+}
+
+/// The wavelengths a path carries, which decide how the RGB colors of materials and lights are turned into the values
+/// the path works with.
+pub trait Wavelengths {
+  type Spectrum: PathSpectrum;
+
+  /// The values of a smooth reflectance spectrum with the given color.
+  fn reflectance(&self, color: &Spectrum) -> Self::Spectrum;
+
+  /// The values of an emission spectrum with the given color, such that white is the renderer's white point.
+  fn illuminant(&self, color: &Spectrum) -> Self::Spectrum;
+
+  /// The wavelength which decides the direction of scattering that depends on wavelength, if wavelengths are tracked.
+  fn hero(&self) -> Option<Real>;
+
+  /// Stops tracking every wavelength but the hero, for paths which only it can have followed, and returns the factor
+  /// which keeps the path's estimate unbiased (which is one if they were already dropped).
+  fn terminate_secondary(&mut self) -> Self::Spectrum;
+}
+
+/// Rendering in RGB, where colors are used as they are.
+pub struct RgbWavelengths;
+
+impl Wavelengths for RgbWavelengths {
+  type Spectrum = Spectrum;
+
+  fn reflectance(&self, color: &Spectrum) -> Spectrum { *color }
+
+  fn illuminant(&self, color: &Spectrum) -> Spectrum { *color }
+
+  fn hero(&self) -> Option<Real> { None }
+
+  fn terminate_secondary(&mut self) -> Spectrum { Spectrum::white() }
+}
+
+#[derive(Debug, Clone, Copy, Add, AddAssign, Mul, MulAssign, Div, DivAssign)]
+pub struct SampledSpectrum {
+  pub inner: na::SVector<Real, NUM_WAVELENGTHS>
+}
+
+impl std::ops::Mul for SampledSpectrum {
+  type Output = SampledSpectrum;
+
+  fn mul(self, rhs: Self) -> Self::Output { Self { inner: self.inner.component_mul(&rhs.inner) } }
+}
+
+impl std::ops::MulAssign for SampledSpectrum {
+  fn mul_assign(&mut self, rhs: Self) { self.inner.component_mul_assign(&rhs.inner) }
+}
+
+impl PathSpectrum for SampledSpectrum {
+  fn none() -> Self { Self { inner: na::SVector::zeros() } }
+
+  fn white() -> Self { Self { inner: na::SVector::repeat(1.0) } }
+}
+
+/// Wavelengths in nanometers chosen by hero wavelength sampling: the first is uniformly distributed over the visible
+/// range, and the rest are spaced evenly after it, wrapping around the range.
+#[derive(Debug, Clone, Copy)]
+pub struct SampledWavelengths {
+  wavelengths: [Real; NUM_WAVELENGTHS],
+  secondary_terminated: bool
+}
+
+impl SampledWavelengths {
+  pub fn sample(u: Real) -> Self {
+    let (min, max) = (VISIBLE_WAVELENGTHS.0 as Real, VISIBLE_WAVELENGTHS.1 as Real);
+    let range = max - min;
+    let hero = min + u * range;
+    Self {
+      wavelengths: std::array::from_fn(|i| {
+        let wavelength = hero + i as Real * range / NUM_WAVELENGTHS as Real;
+        if wavelength > max {
+          wavelength - range
+        } else {
+          wavelength
+        }
+      }),
+      secondary_terminated: false
+    }
+  }
+
+  fn map(&self, f: impl Fn(Real) -> Real) -> SampledSpectrum {
+    SampledSpectrum { inner: na::SVector::from_fn(|i, _| f(self.wavelengths[i])) }
+  }
+
+  /// Estimates the CIE XYZ color of the light with the given values at these wavelengths, relative to the luminance of
+  /// light which has a constant spectrum of one.
+  pub fn to_xyz(self, spectrum: &SampledSpectrum) -> [f64; 3] {
+    let mut xyz = [0.0; 3];
+    for (wavelength, value) in self.wavelengths.iter().zip(spectrum.inner.iter()) {
+      for (sum, weight) in xyz.iter_mut().zip(color_matching(*wavelength as f64)) {
+        *sum += weight * *value as f64;
+      }
+    }
+
+    xyz.map(|c| c / (NUM_WAVELENGTHS as f64 * ILLUMINANT_NORMALIZATION.1))
+  }
+}
+
+/// The average of the CIE Y color matching function, and of D65 weighted by it, over the visible wavelengths.
+static ILLUMINANT_NORMALIZATION: LazyLock<(f64, f64)> = LazyLock::new(|| {
+  let (min, max) = VISIBLE_WAVELENGTHS;
+  let num_steps = (max - min) as usize;
+  let (mut d65_y, mut y) = (0.0, 0.0);
+  for step in 0..=num_steps {
+    let wavelength = min + step as f64;
+    let [_, cmf_y, _] = color_matching(wavelength);
+    d65_y += d65(wavelength) * cmf_y;
+    y += cmf_y;
+  }
+
+  (d65_y / (num_steps + 1) as f64, y / (num_steps + 1) as f64)
+});
+
+impl Wavelengths for SampledWavelengths {
+  type Spectrum = SampledSpectrum;
+
+  fn reflectance(&self, color: &Spectrum) -> SampledSpectrum {
+    // The table only covers sRGB, so colors outside of its gamut are clamped to it. Colors brighter than one are scaled
+    // down into the range the table covers, halfway so they stay smooth.
+    let color = &working_space().convert(color, ColorSpace::LinearSrgb);
+    let largest = color.inner.max();
+    if largest <= 1.0 {
+      let spectrum = RGB_TO_SPECTRUM.spectrum(color);
+      self.map(|wavelength| spectrum.value(wavelength))
+    } else {
+      let scale = 2.0 * largest;
+      let spectrum = RGB_TO_SPECTRUM.spectrum(&(*color / scale));
+      self.map(|wavelength| scale * spectrum.value(wavelength))
+    }
+  }
+
+  fn illuminant(&self, color: &Spectrum) -> SampledSpectrum {
+    let (d65_y, y) = *ILLUMINANT_NORMALIZATION;
+    let reflectance = self.reflectance(color);
+    reflectance * self.map(|wavelength| (d65(wavelength as f64) * y / d65_y) as Real)
+  }
+
+  fn hero(&self) -> Option<Real> { Some(self.wavelengths[0]) }
+
+  fn terminate_secondary(&mut self) -> SampledSpectrum {
+    if self.secondary_terminated {
+      return SampledSpectrum::white();
+    }
+
+    // The hero alone is an estimate of the whole spectrum, with each wavelength it stands in for counted through it
+    self.secondary_terminated = true;
+    let mut weight = SampledSpectrum::none();
+    weight.inner[0] = NUM_WAVELENGTHS as Real;
+    weight
+  }
+}
@@ -0,0 +1,238 @@
+use std::{sync::LazyLock, thread};
+
+use nalgebra as na;
+
+use super::{color_matching, d65, transform_color, Spectrum, SRGB_TO_XYZ, VISIBLE_WAVELENGTHS, XYZ_TO_SRGB};
+use crate::math::Real;
+
+/// The number of points along each axis of the coefficient table.
+const RESOLUTION: usize = 32;
+
+/// The spacing in nanometers of the wavelengths at which spectra are compared while fitting.
+const FIT_STEP: f64 = 5.0;
+
+/// Smooth spectra with every RGB color in the unit cube, following Jakob and Hanika's "A Low-Dimensional Function Space
+/// for Efficient Spectral Upsampling". Each spectrum is a sigmoid of a quadratic in wavelength, whose coefficients are
+/// fitted so that the spectrum's color under D65 is as close as possible in CIELAB to the given RGB color. The
+/// coefficients are tabulated over the largest component of the color (on a grid which is denser near zero and one)
+/// and the ratios of the other two to it, and interpolated between.
+pub struct RgbToSpectrumTable {
+  scale: [f64; RESOLUTION],
+
+  /// Indexed by the largest component, then z, y and x
+  coefficients: Vec<[f64; 3]>
+}
+
+/// A smooth spectrum with values in [0, 1], as the sigmoid of a quadratic in the wavelength (normalized to [0, 1] over
+/// the visible range).
+#[derive(Debug, Clone, Copy)]
+pub struct SigmoidPolynomial {
+  coefficients: [f64; 3]
+}
+
+impl SigmoidPolynomial {
+  pub fn value(&self, wavelength: Real) -> Real {
+    let (min, max) = VISIBLE_WAVELENGTHS;
+    let x = (wavelength as f64 - min) / (max - min);
+    let [c0, c1, c2] = self.coefficients;
+    sigmoid((c0 * x + c1) * x + c2) as Real
+  }
+}
+
+fn sigmoid(x: f64) -> f64 {
+  if x.is_infinite() {
+    if x > 0.0 {
+      1.0
+    } else {
+      0.0
+    }
+  } else {
+    0.5 + x / (2.0 * (1.0 + x * x).sqrt())
+  }
+}
+
+fn smoothstep(x: f64) -> f64 { x * x * (3.0 - 2.0 * x) }
+
+/// The weights which turn the values of a spectrum at the fitting wavelengths into the linear sRGB color of light with
+/// that reflectance under D65, normalized so that a reflectance of one is white.
+struct FitWeights {
+  wavelengths: Vec<f64>,
+  weights: Vec<[f64; 3]>
+}
+
+impl FitWeights {
+  fn new() -> Self {
+    let (min, max) = VISIBLE_WAVELENGTHS;
+    let wavelengths: Vec<_> = (0..=((max - min) / FIT_STEP) as usize).map(|i| min + i as f64 * FIT_STEP).collect();
+    let xyz: Vec<_> = wavelengths.iter().map(|&l| color_matching(l).map(|c| c * d65(l))).collect();
+    let white_y: f64 = xyz.iter().map(|c| c[1]).sum();
+    let weights = xyz.iter().map(|c| transform_color(&XYZ_TO_SRGB, c.map(|c| c / white_y))).collect();
+    Self { wavelengths: wavelengths.iter().map(|l| (l - min) / (max - min)).collect(), weights }
+  }
+
+  fn color(&self, [c0, c1, c2]: [f64; 3]) -> [f64; 3] {
+    let mut rgb = [0.0; 3];
+    for (x, weight) in self.wavelengths.iter().zip(&self.weights) {
+      let s = sigmoid((c0 * x + c1) * x + c2);
+      for (sum, w) in rgb.iter_mut().zip(weight) {
+        *sum += s * w;
+      }
+    }
+
+    rgb
+  }
+}
+
+/// Converts linear sRGB to CIELAB relative to the D65 white point.
+fn lab(rgb: [f64; 3]) -> [f64; 3] {
+  let xyz = transform_color(&SRGB_TO_XYZ, rgb);
+  let white = transform_color(&SRGB_TO_XYZ, [1.0; 3]);
+  let f = |t: f64| {
+    const DELTA: f64 = 6.0 / 29.0;
+    if t > DELTA * DELTA * DELTA {
+      t.cbrt()
+    } else {
+      t / (3.0 * DELTA * DELTA) + 4.0 / 29.0
+    }
+  };
+
+  let [fx, fy, fz] = [0, 1, 2].map(|i| f(xyz[i] / white[i]));
+  [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+/// Refines the coefficients whose spectrum has the color `rgb` with Gauss-Newton iterations, using finite differences
+/// for the Jacobian, and returns the remaining difference between the colors.
+fn fit(weights: &FitWeights, rgb: [f64; 3], coefficients: &mut [f64; 3]) -> f64 {
+  let target = lab(rgb);
+  let residual = |c: [f64; 3]| {
+    let lab = lab(weights.color(c));
+    na::Vector3::new(lab[0] - target[0], lab[1] - target[1], lab[2] - target[2])
+  };
+
+  for _ in 0..15 {
+    let r = residual(*coefficients);
+    if r.norm() < 1e-6 {
+      break;
+    }
+
+    let mut jacobian = na::Matrix3::zeros();
+    for i in 0..3 {
+      const EPSILON: f64 = 1e-5;
+      let (mut below, mut above) = (*coefficients, *coefficients);
+      below[i] -= EPSILON;
+      above[i] += EPSILON;
+      jacobian.set_column(i, &((residual(above) - residual(below)) / (2.0 * EPSILON)));
+    }
+
+    let Some(step) = jacobian.lu().solve(&r) else { break };
+    for (c, s) in coefficients.iter_mut().zip(step.iter()) {
+      *c -= s;
+    }
+
+    // Keep the sigmoid from becoming so steep that it stops being smooth
+    let largest = coefficients.iter().fold(0.0, |m: f64, c| m.max(c.abs()));
+    if largest > 200.0 {
+      *coefficients = coefficients.map(|c| c * 200.0 / largest);
+    }
+  }
+
+  residual(*coefficients).norm()
+}
+
+impl RgbToSpectrumTable {
+  /// Fits every entry of the table, with the grid lines of each largest component fitted in parallel. Fits along the
+  /// z axis start from a moderate brightness and work outwards, each starting from the last one's coefficients.
+  fn new() -> Self {
+    let scale = std::array::from_fn(|k| smoothstep(smoothstep(k as f64 / (RESOLUTION - 1) as f64)));
+    let weights = FitWeights::new();
+    let lines: Vec<_> = (0..3).flat_map(|l| (0..RESOLUTION * RESOLUTION).map(move |ji| (l, ji))).collect();
+    let num_threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let fitted: Vec<Vec<(usize, [[f64; 3]; RESOLUTION])>> = thread::scope(|scope| {
+      let handles: Vec<_> = lines
+        .chunks(lines.len().div_ceil(num_threads))
+        .map(|chunk| {
+          let weights = &weights;
+          scope.spawn(move || {
+            chunk.iter().map(|&(l, ji)| (l * RESOLUTION * RESOLUTION + ji, fit_line(weights, &scale, l, ji))).collect()
+          })
+        })
+        .collect();
+      handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+
+    let mut coefficients = vec![[0.0; 3]; 3 * RESOLUTION * RESOLUTION * RESOLUTION];
+    for (line, line_coefficients) in fitted.into_iter().flatten() {
+      let (l, ji) = (line / (RESOLUTION * RESOLUTION), line % (RESOLUTION * RESOLUTION));
+      for (k, c) in line_coefficients.into_iter().enumerate() {
+        coefficients[(l * RESOLUTION + k) * RESOLUTION * RESOLUTION + ji] = c;
+      }
+    }
+
+    Self { scale, coefficients }
+  }
+
+  /// The spectrum of a color with components in [0, 1].
+  pub fn spectrum(&self, color: &Spectrum) -> SigmoidPolynomial {
+    let rgb = color.inner.map(|c| c.clamp(0.0, 1.0) as f64);
+    if rgb[0] == rgb[1] && rgb[1] == rgb[2] {
+      // Greys have a constant spectrum, which the sigmoid of a constant can match exactly
+      let c = rgb[0];
+      return SigmoidPolynomial { coefficients: [0.0, 0.0, (c - 0.5) / (c * (1.0 - c)).sqrt()] };
+    }
+
+    let l = rgb.imax();
+    let z = rgb[l];
+    let (x, y) = [(l + 1) % 3, (l + 2) % 3].map(|i| rgb[i] * (RESOLUTION - 1) as f64 / z).into();
+    let zi = self.scale.partition_point(|&s| s <= z).clamp(1, RESOLUTION - 1) - 1;
+    let (xi, yi) = ((x as usize).min(RESOLUTION - 2), (y as usize).min(RESOLUTION - 2));
+    let (dx, dy, dz) = (x - xi as f64, y - yi as f64, (z - self.scale[zi]) / (self.scale[zi + 1] - self.scale[zi]));
+
+    let mut coefficients = [0.0; 3];
+    for (k, wz) in [(zi, 1.0 - dz), (zi + 1, dz)] {
+      for (j, wy) in [(yi, 1.0 - dy), (yi + 1, dy)] {
+        for (i, wx) in [(xi, 1.0 - dx), (xi + 1, dx)] {
+          let c = self.coefficients[((l * RESOLUTION + k) * RESOLUTION + j) * RESOLUTION + i];
+          for (sum, c) in coefficients.iter_mut().zip(c) {
+            *sum += wx * wy * wz * c;
+          }
+        }
+      }
+    }
+
+    SigmoidPolynomial { coefficients }
+  }
+}
+
+/// Fits the coefficients along the z axis for the largest component `l` and the grid point `ji` of the other two.
+fn fit_line(weights: &FitWeights, scale: &[f64; RESOLUTION], l: usize, ji: usize) -> [[f64; 3]; RESOLUTION] {
+  let (j, i) = (ji / RESOLUTION, ji % RESOLUTION);
+  let (x, y) = (i as f64 / (RESOLUTION - 1) as f64, j as f64 / (RESOLUTION - 1) as f64);
+  let mut line = [[0.0; 3]; RESOLUTION];
+  let start = RESOLUTION / 5;
+  for ks in [(start..RESOLUTION).collect::<Vec<_>>(), (0..start).rev().collect()] {
+    let mut coefficients = [0.0; 3];
+    for k in ks {
+      let z = scale[k];
+      let mut rgb = [0.0; 3];
+      rgb[l] = z;
+      rgb[(l + 1) % 3] = x * z;
+      rgb[(l + 2) % 3] = y * z;
+      // Starting from the neighbouring fit usually converges fastest, but it can lead the fit astray for saturated
+      // colors, in which case a fresh start does better
+      let error = fit(weights, rgb, &mut coefficients);
+      if error > 1e-3 {
+        let mut fresh = [0.0; 3];
+        if fit(weights, rgb, &mut fresh) < error {
+          coefficients = fresh;
+        }
+      }
+
+      line[k] = coefficients;
+    }
+  }
+
+  line
+}
+
+/// The table for sRGB, which is fitted the first time it is needed.
+pub static RGB_TO_SPECTRUM: LazyLock<RgbToSpectrumTable> = LazyLock::new(RgbToSpectrumTable::new);
@@ -0,0 +1,69 @@
+use std::sync::Arc;
+
+use serde::Deserialize;
+
+use crate::{
+  math::*,
+  textures::{Texture, TextureCoordinate, TextureParameters}
+};
+
+#[derive(Debug, Deserialize)]
+pub struct AlphaMaskParameters {
+  texture: Box<dyn TextureParameters>,
+
+  #[serde(default)]
+  threshold: Option<Real>
+}
+
+impl AlphaMaskParameters {
+  pub fn build_alpha_mask(&self) -> Arc<AlphaMask> {
+    Arc::new(AlphaMask { texture: self.texture.build_texture(), threshold: self.threshold })
+  }
+}
+
+/// Cuts holes in a surface using the alpha channel of a texture. With a threshold, points whose alpha is below it are
+/// removed entirely; otherwise, rays pass through each point with probability one minus its alpha.
+#[derive(Debug)]
+pub struct AlphaMask {
+  texture: Arc<dyn Texture>,
+  threshold: Option<Real>
+}
+
+impl AlphaMask {
+  /// Decides whether the ray with the given origin and direction hits the surface at `point`. The stochastic test is
+  /// driven by a hash of the ray and point rather than a sampler so that it is deterministic for a given ray.
+  pub fn is_opaque(
+    &self,
+    tex_coord: &TextureCoordinate,
+    origin: &WorldPoint,
+    dir: &WorldUnitVector,
+    point: &WorldPoint
+  ) -> bool {
+    let alpha = self.texture.alpha(tex_coord);
+    match self.threshold {
+      Some(threshold) => alpha >= threshold,
+      None if alpha >= 1.0 => true,
+      None if alpha <= 0.0 => false,
+      None => {
+        let dir = dir.into_vector();
+        hash_to_unit_interval(&[origin[0], origin[1], origin[2], dir[0], dir[1], dir[2], point[0], point[1], point[2]])
+          < alpha
+      }
+    }
+  }
+}
+
+/// Hashes some reals into a value uniformly distributed in [0, 1), using the finalizer from MurmurHash3.
+fn hash_to_unit_interval(values: &[Real]) -> Real {
+  let mut h: u64 = 0x9e3779b97f4a7c15;
+  for v in values {
+    h ^= v.to_bits() as u64;
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xff51afd7ed558ccd);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xc4ceb9fe1a85ec53);
+    h ^= h >> 33;
+  }
+
+  ((h >> 40) as Real) / ((1u64 << 24) as Real)
+}
@@ -6,8 +6,14 @@ use serde_derive::Deserialize;
 
 use super::{surface_list::*, *};
 use crate::{
-  duration_to_hms, lights::Light, materials::Material, math::*, raytracing::*, sampling::ContinuousRandomVariable,
-  surfaces::Surface, BuildSettings
+  duration_to_hms,
+  lights::Light,
+  materials::Material,
+  math::*,
+  raytracing::*,
+  sampling::{ContinuousRandomVariable, Sampler},
+  surfaces::Surface,
+  BuildSettings
 };
 
 #[derive(Debug, Clone, Copy, Deserialize)]
@@ -60,10 +66,58 @@ enum BvhNodeType {
 #[derive(Debug)]
 struct BvhNode {
   bounding_box: WorldBoundingBox,
+  emitter_summary: EmitterSummary,
   node_type: BvhNodeType
 }
 
 impl BvhNode {
+  /// The weights of choosing each child of this node (or `None` at a leaf) to light `point`.
+  fn selection_weights(&self, point: &WorldPoint, light_selection: LightSelection) -> Option<[Real; 2]> {
+    match &self.node_type {
+      BvhNodeType::Leaf(_) => None,
+      BvhNodeType::Node(left, right) => Some(
+        [left, right]
+          .map(|child| child.as_ref().map_or(0.0, |child| light_selection.weight(point, &child.emitter_summary)))
+      )
+    }
+  }
+
+  fn sample_direction(
+    &self,
+    point: &WorldPoint,
+    light_selection: LightSelection,
+    sampler: &mut dyn Sampler
+  ) -> Option<WorldUnitVector> {
+    match &self.node_type {
+      BvhNodeType::Leaf(surface_list) => surface_list.sample(point, sampler),
+      BvhNodeType::Node(left, right) => {
+        let weights = self.selection_weights(point, light_selection)?;
+        let child = if sample_weighted(&weights, sampler)?.0 == 0 { left } else { right };
+        child.as_ref()?.sample_direction(point, light_selection, sampler)
+      }
+    }
+  }
+
+  /// The pdf of sampling `ray`'s direction from its origin, summed over every leaf which it passes through.
+  fn direction_pdf(&self, ray: &WorldRay, light_selection: LightSelection) -> Real {
+    if !self.bounding_box.ray_intersects(ray) {
+      return 0.0;
+    }
+
+    match &self.node_type {
+      BvhNodeType::Leaf(surface_list) => surface_list.pdf(&ray.origin(), &ray.dir()).map_or(0.0, |p| p.into_inner()),
+      BvhNodeType::Node(left, right) => {
+        let weights = self.selection_weights(&ray.origin(), light_selection).unwrap_or_default();
+        let total = weights[0] + weights[1];
+        [(left, weights[0]), (right, weights[1])]
+          .iter()
+          .filter(|(_, weight)| *weight > 0.0)
+          .filter_map(|(child, weight)| child.as_ref().map(|c| c.direction_pdf(ray, light_selection) * weight / total))
+          .sum()
+      }
+    }
+  }
+
   fn intersect(&self, ray: &mut WorldRay) -> Option<WorldSurfaceInterface> {
     if self.bounding_box.ray_intersects(&ray) {
       match &self.node_type {
@@ -96,7 +150,8 @@ impl BvhNode {
 
 #[derive(Debug)]
 pub struct BoundingVolumeHierarchy {
-  root_node: BvhNode
+  root_node: BvhNode,
+  light_selection: LightSelection
 }
 
 impl BoundingVolumeHierarchy {
@@ -104,6 +159,7 @@ impl BoundingVolumeHierarchy {
     mut surfaces: Vec<Box<dyn Surface>>,
     partition_strategy: PartitionStrategy,
     max_leaf_primitives: usize,
+    light_selection: LightSelection,
     maybe_progress_bar: Option<ProgressBar>
   ) -> Option<(BvhNode, Vec<Arc<SurfaceList<NoBoxCheck>>>)> {
     let num_surfaces = surfaces.len();
@@ -129,8 +185,9 @@ impl BoundingVolumeHierarchy {
           progress_bar.set_message(duration_to_hms(&projected));
         }
 
-        let leaf = Arc::new(SurfaceList::build(surfaces));
-        Some((BvhNode { bounding_box, node_type: BvhNodeType::Leaf(leaf.clone()) }, vec![leaf]))
+        let leaf = Arc::new(SurfaceList::build(surfaces, light_selection));
+        let emitter_summary = leaf.emitter_summary().clone();
+        Some((BvhNode { bounding_box, emitter_summary, node_type: BvhNodeType::Leaf(leaf.clone()) }, vec![leaf]))
       }
     } else {
       let left: Option<(BvhNode, Vec<Arc<SurfaceList<NoBoxCheck>>>)>;
@@ -220,16 +277,29 @@ impl BoundingVolumeHierarchy {
         }
       };
 
-      left = Self::build_node(left_surfaces, partition_strategy, max_leaf_primitives, maybe_progress_bar.clone());
+      left = Self::build_node(
+        left_surfaces,
+        partition_strategy,
+        max_leaf_primitives,
+        light_selection,
+        maybe_progress_bar.clone()
+      );
 
-      right = Self::build_node(right_surfaces, partition_strategy, max_leaf_primitives, maybe_progress_bar);
+      right =
+        Self::build_node(right_surfaces, partition_strategy, max_leaf_primitives, light_selection, maybe_progress_bar);
 
       let mut leaves = left.as_ref().map(|p| p.1.clone()).unwrap_or_default();
       leaves.append(&mut right.as_ref().map(|p| p.1.clone()).unwrap_or_default());
 
+      let emitter_summary = [&left, &right]
+        .iter()
+        .filter_map(|child| child.as_ref())
+        .fold(EmitterSummary::default(), |acc, (node, _)| acc.merge(&node.emitter_summary));
+
       Some((
         BvhNode {
           bounding_box,
+          emitter_summary,
           node_type: BvhNodeType::Node(left.map(|p| Box::new(p.0)), right.map(|p| Box::new(p.0)))
         },
         leaves
@@ -259,10 +329,12 @@ impl BoundingVolumeHierarchy {
       progress_bar
     });
 
+    let light_selection = settings.light_selection;
     let (root_node, _) =
-      Self::build_node(surfaces, partition_strategy, max_leaf_primitives, maybe_progress_bar.clone()).unwrap();
+      Self::build_node(surfaces, partition_strategy, max_leaf_primitives, light_selection, maybe_progress_bar.clone())
+        .unwrap();
 
-    let s = Self { root_node };
+    let s = Self { root_node, light_selection };
 
     if let Some(progress_bar) = maybe_progress_bar {
       progress_bar.finish();
@@ -275,15 +347,31 @@ impl BoundingVolumeHierarchy {
 impl Surface for BoundingVolumeHierarchy {
   fn intersect_world_ray(&self, ray: &mut WorldRay) -> Option<WorldSurfaceInterface> { self.root_node.intersect(ray) }
 
-  fn random_surface_interface(&self) -> &dyn ContinuousRandomVariable<Param = (), Sample = WorldSurfaceInterface> {
-    todo!()
-  }
-
   fn random_intersecting_direction(
     &self
   ) -> &dyn ContinuousRandomVariable<Param = WorldPoint, Sample = WorldUnitVector> {
-    todo!()
+    self
   }
 
   fn world_bounding_box(&self) -> &WorldBoundingBox { &self.root_node.bounding_box }
+
+  fn emitter_summary(&self) -> &EmitterSummary { &self.root_node.emitter_summary }
+}
+
+impl ContinuousRandomVariable for BoundingVolumeHierarchy {
+  type Param = WorldPoint;
+  type Sample = WorldUnitVector;
+
+  fn sample(&self, point: &WorldPoint, sampler: &mut dyn Sampler) -> Option<WorldUnitVector> {
+    self.root_node.sample_direction(point, self.light_selection, sampler)
+  }
+
+  fn sample_with_pdf(&self, point: &WorldPoint, sampler: &mut dyn Sampler) -> Option<(WorldUnitVector, PositiveReal)> {
+    let dir = self.sample(point, sampler)?;
+    self.pdf(point, &dir).map(|pdf| (dir, pdf))
+  }
+
+  fn pdf(&self, point: &WorldPoint, dir: &WorldUnitVector) -> Option<PositiveReal> {
+    PositiveReal::new(self.root_node.direction_pdf(&Ray::new(*point, *dir), self.light_selection))
+  }
 }
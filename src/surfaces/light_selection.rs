@@ -0,0 +1,91 @@
+use serde::Deserialize;
+
+use crate::{math::*, sampling::Sampler};
+
+/// How groups of surfaces choose which of their parts to sample when looking for emitted light.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub enum LightSelection {
+  /// Every emissive primitive is equally likely to be chosen
+  #[serde(alias = "uniform")]
+  Uniform,
+
+  /// Emissive primitives are chosen in proportion to their power
+  #[default]
+  #[serde(alias = "power")]
+  Power,
+
+  /// Emissive primitives are chosen according to their estimated contribution at the point being lit, found by
+  /// descending a bounding volume hierarchy over them
+  #[serde(alias = "tree")]
+  Tree
+}
+
+impl LightSelection {
+  /// The weight of choosing the emitters summarized by `summary` (relative to their siblings) to light `point`.
+  pub fn weight(self, point: &WorldPoint, summary: &EmitterSummary) -> Real {
+    match self {
+      LightSelection::Uniform => summary.count as Real,
+      LightSelection::Power => summary.power,
+      LightSelection::Tree if summary.power > 0.0 => {
+        // Points too near the emitters (or inside their bounds) treat them as all being half a diagonal away
+        let dist_squared = (summary.bounding_box.center() - *point).norm_squared();
+        let radius_squared = summary.bounding_box.diagonal().norm_squared() / 4.0;
+        summary.power / dist_squared.max(radius_squared).max(Real::MIN_POSITIVE)
+      },
+      LightSelection::Tree => 0.0
+    }
+  }
+}
+
+/// Picks an index with probability proportional to its weight, returning that probability too.
+pub(super) fn sample_weighted(weights: &[Real], sampler: &mut dyn Sampler) -> Option<(usize, Real)> {
+  let total: Real = weights.iter().sum();
+  if total <= 0.0 {
+    return None;
+  }
+
+  let mut remaining = sampler.next().into_inner() * total;
+  let last = weights.iter().rposition(|w| *w > 0.0)?;
+  for (i, w) in weights.iter().enumerate().take(last) {
+    if remaining < *w {
+      return Some((i, w / total));
+    }
+
+    remaining -= w;
+  }
+
+  Some((last, weights[last] / total))
+}
+
+/// The properties of the emissive primitives within a surface which light selection depends on.
+#[derive(Debug, Clone, Default)]
+pub struct EmitterSummary {
+  pub count: usize,
+
+  /// An estimate of the total power (as luminance) emitted
+  pub power: Real,
+
+  /// The bounds of only the emissive primitives
+  pub bounding_box: WorldBoundingBox
+}
+
+impl EmitterSummary {
+  /// The summary of a single primitive with the given bounds and power.
+  pub fn primitive(bounding_box: &WorldBoundingBox, power: Real) -> Self {
+    if power > 0.0 {
+      Self { count: 1, power, bounding_box: bounding_box.clone() }
+    } else {
+      Self::default()
+    }
+  }
+
+  pub fn merge(mut self, other: &EmitterSummary) -> Self {
+    if other.count > 0 {
+      self.count += other.count;
+      self.power += other.power;
+      self.bounding_box.enclose_box(&other.bounding_box);
+    }
+
+    self
+  }
+}
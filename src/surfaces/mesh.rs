@@ -4,14 +4,16 @@ use serde::Deserialize;
 use tobj::LoadOptions;
 
 use super::{
+  alpha_mask::{AlphaMask, AlphaMaskParameters},
   bvh::{BoundingVolumeHierarchy, PartitionStrategy},
   triangle::TriangleSurface,
-  Surface
+  *
 };
 use crate::{
-  lights::{Light, NullLight},
+  lights::Light,
   materials::{Material, NullMaterial},
   math::*,
+  raytracing::ObjectPoint,
   surfaces::SurfaceParameters,
   textures::TextureCoordinate,
   BuildSettings
@@ -118,11 +120,24 @@ pub struct Mesh {
 }
 
 impl Mesh {
+  pub fn surface_area(&self, transform: &LocalToWorld<MeshSpace>) -> Real {
+    self
+      .indices
+      .chunks_exact(3)
+      .map(|vertex_indices| {
+        let [p0, p1, p2] = [0, 1, 2].map(|i| transform.point(&self.vertices[vertex_indices[i]]));
+        (p1 - p0).cross(&(p2 - p0)).norm() / 2.0
+      })
+      .sum()
+  }
+
   pub fn to_triangles(
     &self,
     transform: LocalToWorld<MeshSpace>,
     light: Arc<dyn Light>,
-    material: Arc<dyn Material>
+    material: Arc<dyn Material>,
+    alpha_mask: Option<Arc<AlphaMask>>,
+    visibility: Arc<Visibility>
   ) -> Vec<TriangleSurface> {
     (0..self.indices.len())
       .collect::<Vec<_>>()
@@ -133,11 +148,8 @@ impl Mesh {
           let vi1 = self.indices[i1];
           let vi2 = self.indices[i2];
 
-          let vertices = (
-            transform.point(&self.vertices[vi0]),
-            transform.point(&self.vertices[vi1]),
-            transform.point(&self.vertices[vi2])
-          );
+          let vertices =
+            [vi0, vi1, vi2].map(|i| (transform.point(&self.vertices[i]), ObjectPoint::from(*self.vertices[i].inner())));
 
           let normals = self.vertex_normals.as_ref().map(|(normal_indices, normals)| {
             let ni0 = normal_indices[i0];
@@ -153,7 +165,15 @@ impl Mesh {
             (tex_coords[ti0], tex_coords[ti1], tex_coords[ti2])
           });
 
-          TriangleSurface::new(light.clone(), material.clone(), vertices, normals, tex_coords)
+          TriangleSurface::new(
+            light.clone(),
+            material.clone(),
+            vertices,
+            normals,
+            tex_coords,
+            alpha_mask.clone(),
+            visibility.clone()
+          )
         } else {
           panic!("chunks_exact didn't work!")
         }
@@ -167,7 +187,13 @@ pub struct TriangleMeshSurfaceParameters {
   transform: TransformParameters,
   mesh: String,
   light: Option<String>,
-  material: Option<String>
+  material: Option<String>,
+
+  #[serde(alias = "alpha-mask", default)]
+  alpha_mask: Option<AlphaMaskParameters>,
+
+  #[serde(flatten)]
+  visibility: VisibilityParameters
 }
 
 #[typetag::deserialize(name = "mesh")]
@@ -180,18 +206,16 @@ impl SurfaceParameters for TriangleMeshSurfaceParameters {
     meshes: &HashMap<String, Mesh>,
     settings: BuildSettings
   ) -> Box<dyn Surface> {
-    let triangles = meshes
-      .get(&self.mesh)
-      .unwrap()
+    let mesh = meshes.get(&self.mesh).unwrap();
+    let transform = self.transform.clone().build_transform();
+    let area = mesh.surface_area(&transform);
+    let triangles = mesh
       .to_triangles(
-        self.transform.clone().build_transform(),
-        self.light.as_ref().map(|l| lights.get(l).unwrap().clone()).unwrap_or(Arc::new(NullLight::default())).clone(),
-        self
-          .material
-          .as_ref()
-          .map(|m| materials.get(m).unwrap().clone())
-          .unwrap_or(Arc::new(NullMaterial::default()))
-          .clone()
+        transform,
+        lookup_light(lights, self.light.as_ref(), area),
+        self.material.as_ref().map(|m| materials.get(m).unwrap().clone()).unwrap_or(Arc::new(NullMaterial)).clone(),
+        self.alpha_mask.as_ref().map(|a| a.build_alpha_mask()),
+        self.visibility.build_visibility(self.light.as_ref())
       )
       .into_iter()
       .map(|t| Box::new(t) as Box<dyn Surface>)
@@ -1,15 +1,25 @@
+mod alpha_mask;
 mod bvh;
+mod light_selection;
 mod mesh;
 mod quad;
 mod sphere;
 mod surface;
 mod surface_list;
+#[cfg(test)]
+mod testing;
 mod triangle;
+mod visibility;
 
+pub use light_selection::*;
 pub use mesh::*;
 pub use surface::*;
+pub use visibility::*;
 
-use self::surface_list::SurfaceListParameters;
+use self::{
+  bvh::{BoundingVolumeHierarchy, PartitionStrategy},
+  surface_list::SurfaceListParameters
+};
 use crate::{lights::Light, materials::Material, BuildSettings};
 
 pub fn default_grouping(
@@ -21,3 +31,22 @@ pub fn default_grouping(
 ) -> Box<dyn Surface> {
   (SurfaceListParameters { surfaces }).build_surface(lights, materials, meshes, settings)
 }
+
+/// Groups the emissive surfaces, in a hierarchy if lights are to be chosen by descending a tree over them.
+pub fn emissive_grouping(
+  surfaces: Vec<Box<dyn SurfaceParameters>>,
+  lights: &std::collections::HashMap<String, std::sync::Arc<dyn Light>>,
+  materials: &std::collections::HashMap<String, std::sync::Arc<dyn Material>>,
+  meshes: &std::collections::HashMap<String, Mesh>,
+  settings: BuildSettings
+) -> Box<dyn Surface> {
+  match settings.light_selection {
+    LightSelection::Tree if !surfaces.is_empty() => Box::new(BoundingVolumeHierarchy::build(
+      surfaces.iter().map(|s| s.build_surface(lights, materials, meshes, settings)).collect(),
+      PartitionStrategy::SurfaceAreaHeuristic,
+      1,
+      settings
+    )),
+    _ => default_grouping(surfaces, lights, materials, meshes, settings)
+  }
+}
@@ -3,14 +3,15 @@ use std::{collections::HashMap, sync::Arc};
 use serde::Deserialize;
 
 use super::{
+  alpha_mask::AlphaMaskParameters,
   surface_list::{NoBoxCheck, SurfaceList},
   triangle::TriangleSurface,
   *
 };
 use crate::{
-  lights::NullLight,
   materials::{Material, NullMaterial},
   math::*,
+  raytracing::ObjectPoint,
   textures::TextureCoordinate,
   BuildSettings
 };
@@ -19,7 +20,13 @@ use crate::{
 pub struct QuadSurfaceParameters {
   transform: TransformParameters,
   light: Option<String>,
-  material: Option<String>
+  material: Option<String>,
+
+  #[serde(alias = "alpha-mask", default)]
+  alpha_mask: Option<AlphaMaskParameters>,
+
+  #[serde(flatten)]
+  visibility: VisibilityParameters
 }
 
 #[typetag::deserialize(name = "quad")]
@@ -29,29 +36,51 @@ impl SurfaceParameters for QuadSurfaceParameters {
     lights: &HashMap<String, Arc<dyn Light>>,
     materials: &HashMap<String, Arc<dyn Material>>,
     _: &HashMap<String, Mesh>,
-    _: BuildSettings
+    settings: BuildSettings
   ) -> Box<dyn Surface> {
     let transform: LocalToWorld<WorldSpace> = self.transform.clone().build_transform();
     let normal = transform.normal(&UnitVector3::from_array([0.0, 0.0, 1.0]));
-    let mat =
-      self.material.as_ref().map(|m| materials.get(m).unwrap().clone()).unwrap_or(Arc::new(NullMaterial::default()));
-    let light = self.light.as_ref().map(|l| lights.get(l).unwrap().clone()).unwrap_or(Arc::new(NullLight::default()));
+    let mat = self.material.as_ref().map(|m| materials.get(m).unwrap().clone()).unwrap_or(Arc::new(NullMaterial));
     let normals = Some((normal, normal, normal));
+    let alpha_mask = self.alpha_mask.as_ref().map(|a| a.build_alpha_mask());
+    let visibility = self.visibility.build_visibility(self.light.as_ref());
 
-    let p00 = transform.point(&Point3::from_array([-0.5, -0.5, 0.0]));
-    let p10 = transform.point(&Point3::from_array([0.5, -0.5, 0.0]));
-    let p11 = transform.point(&Point3::from_array([0.5, 0.5, 0.0]));
-    let p01 = transform.point(&Point3::from_array([-0.5, 0.5, 0.0]));
+    let o00 = ObjectPoint::from_array([-0.5, -0.5, 0.0]);
+    let o10 = ObjectPoint::from_array([0.5, -0.5, 0.0]);
+    let o11 = ObjectPoint::from_array([0.5, 0.5, 0.0]);
+    let o01 = ObjectPoint::from_array([-0.5, 0.5, 0.0]);
+    let to_world = |o: &ObjectPoint| transform.point(&Point3::from(*o.inner()));
+    let (p00, p10, p11, p01) = (to_world(&o00), to_world(&o10), to_world(&o11), to_world(&o01));
+    let light = lookup_light(lights, self.light.as_ref(), (p10 - p00).cross(&(p01 - p00)).norm());
 
     let t00 = TextureCoordinate::from_array([0.0, 0.0]);
     let t10 = TextureCoordinate::from_array([1.0, 0.0]);
     let t11 = TextureCoordinate::from_array([1.0, 1.0]);
     let t01 = TextureCoordinate::from_array([0.0, 1.0]);
 
-    Box::new(SurfaceList::<NoBoxCheck>::build(vec![
-      Box::new(TriangleSurface::new(light.clone(), mat.clone(), (p00, p10, p11), normals, Some((t00, t10, t11)))),
-      Box::new(TriangleSurface::new(light, mat, (p00, p11, p01), normals, Some((t00, t11, t01)))),
-    ]))
+    Box::new(SurfaceList::<NoBoxCheck>::build(
+      vec![
+        Box::new(TriangleSurface::new(
+          light.clone(),
+          mat.clone(),
+          [(p00, o00), (p10, o10), (p11, o11)],
+          normals,
+          Some((t00, t10, t11)),
+          alpha_mask.clone(),
+          visibility.clone()
+        )),
+        Box::new(TriangleSurface::new(
+          light,
+          mat,
+          [(p00, o00), (p11, o11), (p01, o01)],
+          normals,
+          Some((t00, t11, t01)),
+          alpha_mask,
+          visibility
+        )),
+      ],
+      settings.light_selection
+    ))
   }
 
   fn has_light(&self) -> bool { self.light.is_some() }
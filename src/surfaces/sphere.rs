@@ -4,12 +4,12 @@ use serde::Deserialize;
 
 use super::*;
 use crate::{
-  lights::{Light, NullLight},
+  lights::Light,
   materials::{Material, NullMaterial},
   math::*,
   raytracing::*,
-  sampling::ContinuousRandomVariable,
-  textures::TextureCoordinate,
+  sampling::{area_to_solid_angle_pdf, uniform_random_on_unit_sphere, ContinuousRandomVariable, Sampler},
+  textures::{TextureCoordinate, TextureFootprint},
   BuildSettings
 };
 
@@ -18,7 +18,10 @@ pub struct SphereSurfaceParameters {
   center: [Real; 3],
   radius: Real,
   light: Option<String>,
-  material: Option<String>
+  material: Option<String>,
+
+  #[serde(flatten)]
+  visibility: VisibilityParameters
 }
 
 #[typetag::deserialize(name = "sphere")]
@@ -33,21 +36,27 @@ impl SurfaceParameters for SphereSurfaceParameters {
     let r = self.radius;
     let radius = PositiveReal::new(r).expect("Sphere radius must be positive");
     let center = Point::from_array(self.center);
-    Box::new(SphereSurface {
-      light: self.material.as_ref().map(|m| lights.get(m).unwrap().clone()).unwrap_or(Arc::new(NullLight::default())),
-      material: self
-        .light
-        .as_ref()
-        .map(|l| materials.get(l).unwrap().clone())
-        .unwrap_or(Arc::new(NullMaterial::default())),
+    let area = 4.0 * PI * r * r;
+    let mut sphere = SphereSurface {
+      light: lookup_light(lights, self.light.as_ref(), area),
+      material: self.material.as_ref().map(|m| materials.get(m).unwrap().clone()).unwrap_or(Arc::new(NullMaterial)),
+      visibility: self.visibility.build_visibility(self.light.as_ref()),
       radius,
       radius_squared: radius * radius,
-      inverse_area: PositiveReal::new_unchecked(1.0 / (4.0 * PI * radius * radius)),
+      inverse_area: PositiveReal::new_unchecked(1.0 / area),
       center,
       bounding_box: {
         BoundingBox3::new(center + nalgebra::vector![-r, -r, -r].into(), center + nalgebra::vector![r, r, r].into())
-      }
-    })
+      },
+      emitter_summary: EmitterSummary::default()
+    };
+
+    // Estimate the power from the radiance leaving either side of a single point
+    let normal = UnitVector::from_array([1.0, 0.0, 0.0]);
+    let point = sphere.surface_point(normal);
+    let radiance = sphere.light.radiance_emitted(&point, &normal) + sphere.light.radiance_emitted(&point, &-normal);
+    sphere.emitter_summary = EmitterSummary::primitive(&sphere.bounding_box, PI * area * radiance.luminance());
+    Box::new(sphere)
   }
 
   fn has_light(&self) -> bool { self.light.is_some() }
@@ -62,15 +71,57 @@ impl Space<3> for SphereSpace {}
 pub struct SphereSurface {
   light: Arc<dyn Light>,
   material: Arc<dyn Material>,
+  visibility: Arc<Visibility>,
   radius: PositiveReal,
   radius_squared: PositiveReal,
   inverse_area: PositiveReal,
   center: WorldPoint,
-  bounding_box: WorldBoundingBox
+  bounding_box: WorldBoundingBox,
+  emitter_summary: EmitterSummary
+}
+
+impl SphereSurface {
+  fn surface_point(&self, normal: WorldUnitVector) -> WorldSurfacePoint {
+    let n = normal.inner();
+    let phi = n.y.atan2(n.x);
+    let theta = n.z.asin();
+    let u = (phi + PI) * INV_PI / 2.0;
+    let v = (theta + PI / 2.0) * INV_PI;
+
+    // Differentiate the spherical parameterization above, since d(phi)/du = 2 pi and d(theta)/dv = pi
+    let r = self.radius.into_inner();
+    let rho = (n.x * n.x + n.y * n.y).sqrt();
+    let (dpdu, dpdv) = if rho < 1e-6 {
+      let (t, b) = normal.orthonormal_basis();
+      (t * (2.0 * PI * r), b * (PI * r))
+    } else {
+      let dpdu = WorldVector::from_array([-n.y, n.x, 0.0]) * (2.0 * PI * r);
+      let dpdv = WorldVector::from_array([-n.x * n.z / rho, -n.y * n.z / rho, rho]) * (PI * r);
+      (dpdu, dpdv)
+    };
+
+    SurfacePoint {
+      point: self.center + normal * r,
+      geometric_normal: normal,
+      shading_normal: normal,
+      tex_coord: TextureCoordinate::from_array([u, v]),
+      shading_tangent: dpdu.normalize(),
+      dpdu,
+      dpdv,
+      footprint: TextureFootprint::point(),
+      object_point: ObjectPoint::from_array([n.x, n.y, n.z]),
+      object_dpdu: ObjectVector::from(*dpdu.inner()) * (1.0 / r),
+      object_dpdv: ObjectVector::from(*dpdv.inner()) * (1.0 / r)
+    }
+  }
 }
 
 impl Surface for SphereSurface {
   fn intersect_world_ray(&self, ray: &mut WorldRay) -> Option<WorldSurfaceInterface> {
+    if !self.visibility.is_visible_to(ray.kind()) {
+      return None;
+    }
+
     let o_minus_c = ray.origin() - self.center;
     let b = 2.0 * Vector3::from(ray.dir()).dot(&o_minus_c);
     let c = o_minus_c.norm_squared() - self.radius_squared;
@@ -99,36 +150,145 @@ impl Surface for SphereSurface {
       return None;
     }
 
-    let normal = (p - self.center).normalize();
-
-    let n = normal.inner();
-    let phi = n.y.atan2(n.x);
-    let theta = n.z.asin();
-    let u = (phi + PI) * INV_PI / 2.0;
-    let v = (theta + PI / 2.0) * INV_PI;
-
     Some(SurfaceInterface {
-      surface_point: SurfacePoint {
-        point: self.center + normal * self.radius.into_inner(),
-        geometric_normal: normal,
-        shading_normal: normal,
-        tex_coord: TextureCoordinate::from_array([u, v])
-      },
+      surface_point: self.surface_point((p - self.center).normalize()),
       light: self.light.as_ref(),
       material: self.material.as_ref(),
+      visibility: self.visibility.as_ref(),
       intersect_dist: t
     })
   }
 
-  fn random_surface_interface(&self) -> &dyn ContinuousRandomVariable<Param = (), Sample = WorldSurfaceInterface> {
-    todo!()
-  }
-
   fn random_intersecting_direction(
     &self
   ) -> &dyn ContinuousRandomVariable<Param = WorldPoint, Sample = WorldUnitVector> {
-    todo!()
+    self
   }
 
   fn world_bounding_box(&self) -> &WorldBoundingBox { &self.bounding_box }
+
+  fn emitter_summary(&self) -> &EmitterSummary { &self.emitter_summary }
+}
+
+impl SphereSurface {
+  /// The cone of directions from `point` which meet the sphere, as its axis and one minus the cosine of its half-angle,
+  /// or `None` if the point is inside the sphere.
+  fn visible_cone(&self, point: &WorldPoint) -> Option<(WorldUnitVector, Real)> {
+    let (axis, dist) = (self.center - *point).normalize_with_norm();
+    let sin_squared = self.radius_squared.into_inner() / (dist * dist);
+    if sin_squared >= 1.0 {
+      return None;
+    }
+
+    // Use a Taylor expansion for narrow cones, where subtracting the cosine from one would lose all precision
+    let one_minus_cos = if sin_squared < 0.00068523 { sin_squared / 2.0 } else { 1.0 - (1.0 - sin_squared).sqrt() };
+    Some((axis, one_minus_cos))
+  }
+}
+
+impl ContinuousRandomVariable for SphereSurface {
+  type Param = WorldPoint;
+  type Sample = WorldUnitVector;
+
+  fn sample(&self, point: &WorldPoint, sampler: &mut dyn Sampler) -> Option<WorldUnitVector> {
+    // From outside, sample the cone of directions towards the sphere uniformly, otherwise sample uniformly by area
+    match self.visible_cone(point) {
+      Some((axis, one_minus_cos)) => {
+        let cos_theta = 1.0 - sampler.next().into_inner() * one_minus_cos;
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let (sin_phi, cos_phi) = sampler.random_in_closed_open(0.0, 2.0 * PI).sin_cos();
+        let (t, b) = axis.orthonormal_basis();
+        Some((t * (sin_theta * cos_phi) + b * (sin_theta * sin_phi) + axis * cos_theta).normalize())
+      },
+      None => {
+        let normal: WorldUnitVector = uniform_random_on_unit_sphere(sampler);
+        Some((self.center + normal * self.radius.into_inner() - *point).normalize())
+      }
+    }
+  }
+
+  fn sample_with_pdf(&self, point: &WorldPoint, sampler: &mut dyn Sampler) -> Option<(WorldUnitVector, PositiveReal)> {
+    let dir = self.sample(point, sampler)?;
+    self.pdf(point, &dir).map(|pdf| (dir, pdf))
+  }
+
+  fn pdf(&self, point: &WorldPoint, dir: &WorldUnitVector) -> Option<PositiveReal> {
+    let o_minus_c = *point - self.center;
+    let b = Vector3::from(*dir).dot(&o_minus_c);
+    let discriminant = b * b - (o_minus_c.norm_squared() - self.radius_squared.into_inner());
+    let root = discriminant.max(0.0).sqrt();
+    if discriminant <= 0.0 || root - b <= 0.0 {
+      return None;
+    }
+
+    if let Some((_, one_minus_cos)) = self.visible_cone(point) {
+      return PositiveReal::new(1.0 / (2.0 * PI * one_minus_cos));
+    }
+
+    // A direction may have been sampled from either point where it meets the sphere, and at both of them the cosine
+    // with the normal is the same
+    let dist_squared: Real = [-b - root, -b + root].iter().filter(|t| **t > 0.0).map(|t| t * t).sum();
+    area_to_solid_angle_pdf(self.inverse_area.into_inner(), dist_squared.sqrt(), root / self.radius.into_inner())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::collections::HashMap;
+
+  use super::{super::testing::*, *};
+
+  const STRATA: usize = 64;
+  const SAMPLES: usize = 10_000;
+
+  fn sphere(center: [Real; 3], radius: Real) -> Box<dyn Surface> {
+    let parameters = SphereSurfaceParameters {
+      center,
+      radius,
+      light: None,
+      material: None,
+      visibility: serde_json::from_str("{}").unwrap()
+    };
+    let settings =
+      BuildSettings { num_threads: 1, use_progress_bar: false, light_selection: LightSelection::default() };
+    parameters.build_surface(&HashMap::new(), &HashMap::new(), &HashMap::new(), settings)
+  }
+
+  #[test]
+  fn pdf_integrates_to_one_from_outside() {
+    let (center, radius) = ([0.5, -1.0, 2.0], 1.5);
+    let surface = sphere(center, radius);
+    for point in [[0.5, -1.0, 20.0], [3.0, 1.0, 4.0], [0.5, -1.0, 3.6]] {
+      let (point, center) = (WorldPoint::from_array(point), WorldPoint::from_array(center));
+      let (axis, dist) = (center - point).normalize_with_norm();
+
+      // Integrate over a cone a little wider than the one the sphere fills
+      let sin_max = (1.1 * radius / dist).min(1.0);
+      let cos_max = (1.0 - sin_max * sin_max).sqrt();
+      let integral = integrate_pdf(surface.random_intersecting_direction(), &point, &axis, cos_max, STRATA);
+      assert!((integral - 1.0).abs() < 0.01, "pdf integrates to {integral} from {point:?}");
+    }
+  }
+
+  #[test]
+  fn pdf_integrates_to_one_from_inside() {
+    let (center, radius) = ([0.5, -1.0, 2.0], 1.5);
+    let surface = sphere(center, radius);
+    let axis = UnitVector::from_array([0.0, 0.0, 1.0]);
+    for point in [[0.5, -1.0, 2.0], [1.0, -0.5, 1.5], [0.5, -1.0, 3.45]] {
+      let point = WorldPoint::from_array(point);
+      let integral = integrate_pdf(surface.random_intersecting_direction(), &point, &axis, -1.0, STRATA);
+      assert!((integral - 1.0).abs() < 0.01, "pdf integrates to {integral} from {point:?}");
+    }
+  }
+
+  #[test]
+  fn sampled_pdfs_match_pdf() {
+    let surface = sphere([0.5, -1.0, 2.0], 1.5);
+    for point in [[0.5, -1.0, 20.0], [3.0, 1.0, 4.0], [0.5, -1.0, 3.6], [0.5, -1.0, 2.0], [0.5, -1.0, 3.45]] {
+      let point = WorldPoint::from_array(point);
+      let mismatched = mismatched_pdf_fraction(surface.random_intersecting_direction(), &point, SAMPLES, 1e-3);
+      assert!(mismatched < 1e-3, "{mismatched} of the sampled pdfs from {point:?} differ");
+    }
+  }
 }
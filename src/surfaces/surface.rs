@@ -1,8 +1,13 @@
 use std::{collections::HashMap, fmt::Debug, sync::Arc};
 
-use super::Mesh;
+use super::{EmitterSummary, Mesh};
 use crate::{
-  lights::Light, materials::Material, math::*, raytracing::*, sampling::ContinuousRandomVariable, BuildSettings
+  lights::{Light, NullLight},
+  materials::Material,
+  math::*,
+  raytracing::*,
+  sampling::ContinuousRandomVariable,
+  BuildSettings
 };
 
 #[typetag::deserialize(tag = "type")]
@@ -18,14 +23,28 @@ pub trait SurfaceParameters: Debug {
   fn has_light(&self) -> bool;
 }
 
+/// Looks up the light named by a surface (if any), adapted to the total area of that surface.
+pub(super) fn lookup_light(
+  lights: &HashMap<String, Arc<dyn Light>>,
+  name: Option<&String>,
+  area: Real
+) -> Arc<dyn Light> {
+  name.map_or(Arc::new(NullLight), |name| {
+    let light = lights.get(name).unwrap_or_else(|| panic!("Light \"{name}\" does not exist!"));
+    PositiveReal::new(area).and_then(|area| light.with_surface_area(area)).unwrap_or(light.clone())
+  })
+}
+
 pub trait Surface: Debug {
   fn intersect_world_ray(&self, ray: &mut WorldRay) -> Option<WorldSurfaceInterface>;
 
-  fn random_surface_interface(&self) -> &dyn ContinuousRandomVariable<Param = (), Sample = WorldSurfaceInterface>;
-
+  /// Samples directions from a point towards the emissive parts of this surface. The pdf of a direction counts every
+  /// part of the surface lying that way, not only the nearest, since it may have been sampled from any of them.
   fn random_intersecting_direction(
     &self
   ) -> &dyn ContinuousRandomVariable<Param = WorldPoint, Sample = WorldUnitVector>;
 
   fn world_bounding_box(&self) -> &WorldBoundingBox;
+
+  fn emitter_summary(&self) -> &EmitterSummary;
 }
@@ -1,10 +1,15 @@
-use std::{marker::PhantomData, sync::Arc};
+use std::{fmt::Debug, marker::PhantomData, sync::Arc};
 
 use serde::Deserialize;
 
 use super::*;
 use crate::{
-  materials::Material, math::*, raytracing::*, sampling::ContinuousRandomVariable, surfaces::Surface, BuildSettings
+  materials::Material,
+  math::*,
+  raytracing::*,
+  sampling::{ContinuousRandomVariable, Sampler},
+  surfaces::Surface,
+  BuildSettings
 };
 
 #[derive(Debug, Deserialize)]
@@ -23,7 +28,8 @@ impl SurfaceParameters for SurfaceListParameters {
     settings: BuildSettings
   ) -> Box<dyn Surface> {
     Box::new(SurfaceList::<BoxCheck>::build(
-      self.surfaces.iter().map(|s| s.build_surface(lights, materials, meshes, settings)).collect()
+      self.surfaces.iter().map(|s| s.build_surface(lights, materials, meshes, settings)).collect(),
+      settings.light_selection
     ))
   }
 
@@ -38,27 +44,64 @@ pub struct NoBoxCheck;
 
 #[derive(Debug)]
 pub struct SurfaceList<Variant> {
-  inverse_num_surfaces: PositiveReal,
   surfaces: Vec<(Box<dyn Surface>, WorldBoundingBox)>,
   bounding_box: WorldBoundingBox,
+  light_selection: LightSelection,
+  emitter_summary: EmitterSummary,
   _phantom: PhantomData<Variant>
 }
 
 impl<T> SurfaceList<T> {
-  pub fn build(surfaces: Vec<Box<dyn Surface>>) -> Self {
+  pub fn build(surfaces: Vec<Box<dyn Surface>>, light_selection: LightSelection) -> Self {
     let bboxes: Vec<_> = surfaces.iter().map(|s| s.world_bounding_box().clone()).collect();
     let bounding_box = bboxes.iter().fold(WorldBoundingBox::default(), |mut acc, b| {
       acc.enclose_box(b);
       acc
     });
 
+    let emitter_summary = surfaces.iter().fold(EmitterSummary::default(), |acc, s| acc.merge(s.emitter_summary()));
     Self {
-      inverse_num_surfaces: PositiveReal::new_unchecked(1.0 / surfaces.len() as Real),
       surfaces: surfaces.into_iter().zip(bboxes.into_iter()).collect(),
       bounding_box,
+      light_selection,
+      emitter_summary,
       _phantom: PhantomData::default()
     }
   }
+
+  fn selection_weights(&self, point: &WorldPoint) -> Vec<Real> {
+    self.surfaces.iter().map(|(s, _)| self.light_selection.weight(point, s.emitter_summary())).collect()
+  }
+}
+
+impl<T: Debug> ContinuousRandomVariable for SurfaceList<T> {
+  type Param = WorldPoint;
+  type Sample = WorldUnitVector;
+
+  fn sample(&self, point: &WorldPoint, sampler: &mut dyn Sampler) -> Option<WorldUnitVector> {
+    let (index, _) = sample_weighted(&self.selection_weights(point), sampler)?;
+    self.surfaces[index].0.random_intersecting_direction().sample(point, sampler)
+  }
+
+  fn sample_with_pdf(&self, point: &WorldPoint, sampler: &mut dyn Sampler) -> Option<(WorldUnitVector, PositiveReal)> {
+    let dir = self.sample(point, sampler)?;
+    self.pdf(point, &dir).map(|pdf| (dir, pdf))
+  }
+
+  fn pdf(&self, point: &WorldPoint, dir: &WorldUnitVector) -> Option<PositiveReal> {
+    let weights = self.selection_weights(point);
+    let total: Real = weights.iter().sum();
+    let ray = Ray::new(*point, *dir);
+    let pdf = self
+      .surfaces
+      .iter()
+      .zip(weights)
+      .filter(|((_, bbox), weight)| *weight > 0.0 && bbox.ray_intersects(&ray))
+      .filter_map(|((s, _), weight)| s.random_intersecting_direction().pdf(point, dir).map(|p| p.into_inner() * weight))
+      .sum::<Real>();
+
+    PositiveReal::new(pdf / total)
+  }
 }
 
 impl Surface for SurfaceList<BoxCheck> {
@@ -76,17 +119,15 @@ impl Surface for SurfaceList<BoxCheck> {
     closest
   }
 
-  fn random_surface_interface(&self) -> &dyn ContinuousRandomVariable<Param = (), Sample = WorldSurfaceInterface> {
-    todo!()
-  }
-
   fn random_intersecting_direction(
     &self
   ) -> &dyn ContinuousRandomVariable<Param = WorldPoint, Sample = WorldUnitVector> {
-    todo!()
+    self
   }
 
   fn world_bounding_box(&self) -> &WorldBoundingBox { &self.bounding_box }
+
+  fn emitter_summary(&self) -> &EmitterSummary { &self.emitter_summary }
 }
 
 impl Surface for SurfaceList<NoBoxCheck> {
@@ -102,15 +143,13 @@ impl Surface for SurfaceList<NoBoxCheck> {
     closest
   }
 
-  fn random_surface_interface(&self) -> &dyn ContinuousRandomVariable<Param = (), Sample = WorldSurfaceInterface> {
-    todo!()
-  }
-
   fn random_intersecting_direction(
     &self
   ) -> &dyn ContinuousRandomVariable<Param = WorldPoint, Sample = WorldUnitVector> {
-    todo!()
+    self
   }
 
   fn world_bounding_box(&self) -> &WorldBoundingBox { &self.bounding_box }
+
+  fn emitter_summary(&self) -> &EmitterSummary { &self.emitter_summary }
 }
@@ -0,0 +1,53 @@
+//! Monte Carlo checks of how surfaces sample the directions towards themselves, shared by their tests.
+
+use crate::{math::*, sampling::*};
+
+/// Estimates the integral of the pdf of directions from `point` over the cone about `axis` whose half-angle has cosine
+/// `cos_max`, which should hold every direction meeting the surface. This is one if the pdf is normalized. The cone is
+/// stratified into a jittered grid of `strata` by `strata` cells, so that few directions are needed.
+pub fn integrate_pdf(
+  directions: &dyn ContinuousRandomVariable<Param = WorldPoint, Sample = WorldUnitVector>,
+  point: &WorldPoint,
+  axis: &WorldUnitVector,
+  cos_max: Real,
+  strata: usize
+) -> Real {
+  let mut sampler = IndependentSampler::seeded(1);
+  let (t, b) = axis.orthonormal_basis();
+  let one_minus_cos = 1.0 - cos_max;
+  let mut sum = 0.0;
+  for i in 0..strata {
+    for j in 0..strata {
+      let u = (i as Real + sampler.next().into_inner()) / strata as Real;
+      let v = (j as Real + sampler.next().into_inner()) / strata as Real;
+      let cos_theta = 1.0 - u * one_minus_cos;
+      let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+      let (sin_phi, cos_phi) = (2.0 * PI * v).sin_cos();
+      let dir = (t * (sin_theta * cos_phi) + b * (sin_theta * sin_phi) + *axis * cos_theta).normalize();
+      sum += directions.pdf(point, &dir).map_or(0.0, |pdf| pdf.into_inner() as f64);
+    }
+  }
+
+  (sum / (strata * strata) as f64) as Real * 2.0 * PI * one_minus_cos
+}
+
+/// The fraction of directions sampled from `point` whose pdf is not within a relative `tolerance` of the one `pdf`
+/// gives for them.
+pub fn mismatched_pdf_fraction(
+  directions: &dyn ContinuousRandomVariable<Param = WorldPoint, Sample = WorldUnitVector>,
+  point: &WorldPoint,
+  samples: usize,
+  tolerance: Real
+) -> Real {
+  let mut sampler = IndependentSampler::seeded(2);
+  let mismatched = (0..samples)
+    .filter(|_| match directions.sample_with_pdf(point, &mut sampler) {
+      Some((dir, sampled)) => directions
+        .pdf(point, &dir)
+        .is_none_or(|pdf| (pdf.into_inner() - sampled.into_inner()).abs() > tolerance * sampled.into_inner()),
+      None => true
+    })
+    .count();
+
+  mismatched as Real / samples as Real
+}
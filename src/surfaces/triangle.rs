@@ -1,9 +1,13 @@
-use std::sync::Arc;
+use std::{f64, sync::Arc};
 
-use super::*;
+use super::{alpha_mask::AlphaMask, visibility::Visibility, *};
 use crate::{
-  lights::Light, materials::Material, math::*, raytracing::*, sampling::ContinuousRandomVariable,
-  textures::TextureCoordinate
+  lights::Light,
+  materials::Material,
+  math::*,
+  raytracing::*,
+  sampling::{area_to_solid_angle_pdf, ContinuousRandomVariable, Sampler},
+  textures::{TextureCoordinate, TextureFootprint}
 };
 
 type VertexInfo = (WorldPoint, WorldUnitVector, TextureCoordinate);
@@ -12,22 +16,34 @@ type VertexInfo = (WorldPoint, WorldUnitVector, TextureCoordinate);
 pub struct TriangleSurface {
   light: Arc<dyn Light>,
   material: Arc<dyn Material>,
+  alpha_mask: Option<Arc<AlphaMask>>,
+  visibility: Arc<Visibility>,
   v0: VertexInfo,
   v1: VertexInfo,
   v2: VertexInfo,
+  object_points: [ObjectPoint; 3],
   edge1: WorldVector,
   edge2: WorldVector,
   outer_normal: WorldUnitVector,
-  bounding_box: WorldBoundingBox
+  dpdu: WorldVector,
+  dpdv: WorldVector,
+  object_dpdu: ObjectVector,
+  object_dpdv: ObjectVector,
+  tangent: WorldUnitVector,
+  area: Real,
+  bounding_box: WorldBoundingBox,
+  emitter_summary: EmitterSummary
 }
 
 impl TriangleSurface {
   pub fn new(
     light: Arc<dyn Light>,
     material: Arc<dyn Material>,
-    (p0, p1, p2): (WorldPoint, WorldPoint, WorldPoint),
+    [(p0, o0), (p1, o1), (p2, o2)]: [(WorldPoint, ObjectPoint); 3],
     maybe_normals: Option<(WorldUnitVector, WorldUnitVector, WorldUnitVector)>,
-    maybe_tex_coords: Option<(TextureCoordinate, TextureCoordinate, TextureCoordinate)>
+    maybe_tex_coords: Option<(TextureCoordinate, TextureCoordinate, TextureCoordinate)>,
+    alpha_mask: Option<Arc<AlphaMask>>,
+    visibility: Arc<Visibility>
   ) -> Self {
     let mut bounding_box = WorldBoundingBox::default();
     bounding_box.enclose_point(&p0);
@@ -56,19 +72,78 @@ impl TriangleSurface {
       t2 = TextureCoordinate::from_array([0.0, 1.0]);
     }
 
+    // Solve for the derivatives of position with respect to the texture coordinates, which are constant over the
+    // triangle. If the texture coordinates are degenerate, any tangent basis is as good as any other.
+    let (duv02, duv12) = (t0 - t2, t1 - t2);
+    let (dp02, dp12) = (p0 - p2, p1 - p2);
+    let det = duv02[0] * duv12[1] - duv02[1] * duv12[0];
+    let (dpdu, dpdv) = if det.abs() < 1e-8 {
+      let (t, b) = outer_normal.orthonormal_basis();
+      (t.into_vector(), b.into_vector())
+    } else {
+      let inv_det = 1.0 / det;
+      ((dp02 * duv12[1] - dp12 * duv02[1]) * inv_det, (dp12 * duv02[0] - dp02 * duv12[0]) * inv_det)
+    };
+
+    // The same derivatives in object space, which solid textures are looked up in
+    let (object_dpdu, object_dpdv) = if det.abs() < 1e-8 {
+      (ObjectVector::zero(), ObjectVector::zero())
+    } else {
+      let (do02, do12, inv_det) = (o0 - o2, o1 - o2, 1.0 / det);
+      ((do02 * duv12[1] - do12 * duv02[1]) * inv_det, (do12 * duv02[0] - do02 * duv12[0]) * inv_det)
+    };
+
+    let tangent = dpdu.normalize();
+
+    // Estimate the power from the radiance leaving either side of the centroid
+    let area = edge1.cross(&edge2).norm() / 2.0;
+    let centroid = SurfacePoint {
+      point: p0 * (1.0 / 3.0) + (p1 * (1.0 / 3.0)).into() + (p2 * (1.0 / 3.0)).into(),
+      geometric_normal: outer_normal,
+      shading_normal: outer_normal,
+      tex_coord: (t0 + t1 + t2) * (1.0 / 3.0),
+      shading_tangent: tangent,
+      dpdu,
+      dpdv,
+      footprint: TextureFootprint::point(),
+      object_point: o0 * (1.0 / 3.0) + (o1 * (1.0 / 3.0)).into() + (o2 * (1.0 / 3.0)).into(),
+      object_dpdu,
+      object_dpdv
+    };
+
+    let radiance = light.radiance_emitted(&centroid, &outer_normal) + light.radiance_emitted(&centroid, &-outer_normal);
+    let emitter_summary = EmitterSummary::primitive(&bounding_box, PI * area * radiance.luminance());
+
     let v0 = (p0, n0, t0);
     let v1 = (p1, n1, t1);
     let v2 = (p2, n2, t2);
 
-    Self { v0, v1, v2, edge1, edge2, outer_normal, bounding_box, material, light }
+    Self {
+      v0,
+      v1,
+      v2,
+      object_points: [o0, o1, o2],
+      edge1,
+      edge2,
+      outer_normal,
+      dpdu,
+      dpdv,
+      object_dpdu,
+      object_dpdv,
+      tangent,
+      area,
+      bounding_box,
+      emitter_summary,
+      material,
+      light,
+      alpha_mask,
+      visibility
+    }
   }
-}
 
-impl Surface for TriangleSurface {
-  fn intersect_world_ray(&self, ray: &mut WorldRay) -> Option<WorldSurfaceInterface> {
-    let (p0, n0, t0) = self.v0;
-    let (p1, n1, t1) = self.v1;
-    let (p2, n2, t2) = self.v2;
+  /// Finds how far along the ray it meets the triangle, and where on it as barycentric coordinates, ignoring the alpha
+  /// mask.
+  fn locate_hit(&self, ray: &WorldRay) -> Option<(PositiveReal, [Real; 3])> {
     let dir = ray.dir().into_vector();
 
     let pvec = dir.cross(&self.edge2);
@@ -78,7 +153,7 @@ impl Surface for TriangleSurface {
     }
 
     let inv_det = 1.0 / det;
-    let tvec = ray.origin() - p0;
+    let tvec = ray.origin() - self.v0.0;
     let u = tvec.dot(&pvec) * inv_det;
     let qvec = tvec.cross(&self.edge1);
     let v = dir.dot(&qvec) * inv_det;
@@ -86,37 +161,274 @@ impl Surface for TriangleSurface {
       return None;
     }
 
-    let t = self.edge2.dot(&qvec) * inv_det;
-    let barycentric_coords = [1.0 - (u + v), u, v];
-
-    ray.at_real(t).map(|(t, _)| {
-      let p = p0 * barycentric_coords[0] + (p1 * barycentric_coords[1]).into() + (p2 * barycentric_coords[2]).into();
-      let sn = (n0 * barycentric_coords[0] + n1 * barycentric_coords[1] + n2 * barycentric_coords[2]).normalize();
-      let uv = t0 * barycentric_coords[0] + t1 * barycentric_coords[1] + t2 * barycentric_coords[2];
-
-      SurfaceInterface {
-        surface_point: SurfacePoint {
-          point: p,
-          geometric_normal: self.outer_normal,
-          shading_normal: sn,
-          tex_coord: uv
-        },
-        light: self.light.as_ref(),
-        material: self.material.as_ref(),
-        intersect_dist: t
+    let (t, _) = ray.at_real(self.edge2.dot(&qvec) * inv_det)?;
+    Some((t, [1.0 - (u + v), u, v]))
+  }
+
+  /// Intersects the ray with the triangle regardless of which rays can see it.
+  fn intersect(&self, ray: &WorldRay) -> Option<WorldSurfaceInterface<'_>> {
+    let (p0, n0, t0) = self.v0;
+    let (p1, n1, t1) = self.v1;
+    let (p2, n2, t2) = self.v2;
+    let (t, barycentric_coords) = self.locate_hit(ray)?;
+    let p = p0 * barycentric_coords[0] + (p1 * barycentric_coords[1]).into() + (p2 * barycentric_coords[2]).into();
+    let uv = t0 * barycentric_coords[0] + t1 * barycentric_coords[1] + t2 * barycentric_coords[2];
+    let [o0, o1, o2] = self.object_points;
+
+    // Masked-out hits are skipped without shrinking the ray, so anything behind them can still be found
+    if let Some(alpha_mask) = &self.alpha_mask {
+      if !alpha_mask.is_opaque(&uv, &ray.origin(), &ray.dir(), &p) {
+        return None;
       }
+    }
+
+    let sn = (n0 * barycentric_coords[0] + n1 * barycentric_coords[1] + n2 * barycentric_coords[2]).normalize();
+    Some(SurfaceInterface {
+      surface_point: SurfacePoint {
+        point: p,
+        geometric_normal: self.outer_normal,
+        shading_normal: sn,
+        tex_coord: uv,
+        shading_tangent: self.tangent,
+        dpdu: self.dpdu,
+        dpdv: self.dpdv,
+        footprint: TextureFootprint::point(),
+        object_point: o0 * barycentric_coords[0]
+          + (o1 * barycentric_coords[1]).into()
+          + (o2 * barycentric_coords[2]).into(),
+        object_dpdu: self.object_dpdu,
+        object_dpdv: self.object_dpdv
+      },
+      light: self.light.as_ref(),
+      material: self.material.as_ref(),
+      visibility: self.visibility.as_ref(),
+      intersect_dist: t
     })
   }
+}
+
+impl Surface for TriangleSurface {
+  fn intersect_world_ray(&self, ray: &mut WorldRay) -> Option<WorldSurfaceInterface> {
+    if !self.visibility.is_visible_to(ray.kind()) {
+      return None;
+    }
 
-  fn random_surface_interface(&self) -> &dyn ContinuousRandomVariable<Param = (), Sample = WorldSurfaceInterface> {
-    todo!()
+    self.intersect(ray)
   }
 
   fn random_intersecting_direction(
     &self
   ) -> &dyn ContinuousRandomVariable<Param = WorldPoint, Sample = WorldUnitVector> {
-    todo!()
+    self
   }
 
   fn world_bounding_box(&self) -> &WorldBoundingBox { &self.bounding_box }
+
+  fn emitter_summary(&self) -> &EmitterSummary { &self.emitter_summary }
+}
+
+/// Spherical triangles are worked with in double precision, as sampling small ones in single precision loses enough
+/// to pile samples up along their edges.
+type RawVector = nalgebra::Vector3<f64>;
+
+/// Below this solid angle, sampling the spherical triangle suffers from round-off error, and above it the triangle
+/// surrounds the point so closely that sampling by area is better anyway.
+const MIN_SPHERICAL_SAMPLE_AREA: f64 = 3e-4;
+const MAX_SPHERICAL_SAMPLE_AREA: f64 = 6.22;
+
+/// Finds the angle between two unit vectors without losing precision when they are nearly parallel.
+fn angle_between(v1: &RawVector, v2: &RawVector) -> f64 {
+  if v1.dot(v2) < 0.0 {
+    f64::consts::PI - 2.0 * ((v1 + v2).norm() / 2.0).min(1.0).asin()
+  } else {
+    2.0 * ((v2 - v1).norm() / 2.0).min(1.0).asin()
+  }
+}
+
+/// The part of `v` orthogonal to the unit vector `w`, normalized.
+fn gram_schmidt(v: &RawVector, w: &RawVector) -> RawVector { (v - w * v.dot(w)).normalize() }
+
+/// A triangle projected onto the unit sphere about a point, with vertices `a`, `b` and `c`.
+struct SphericalTriangle {
+  a: RawVector,
+  b: RawVector,
+  c: RawVector,
+
+  /// The interior angle at `a`
+  alpha: f64,
+  solid_angle: f64
+}
+
+impl SphericalTriangle {
+  /// Maps a point in the unit square to a direction uniformly distributed over the triangle, using Arvo's method of
+  /// first choosing the sub-triangle with the right fraction of the area and then a point along its far edge.
+  fn sample(&self, u: Real, v: Real) -> Option<WorldUnitVector> {
+    let (a, b, c) = (&self.a, &self.b, &self.c);
+    let (u, v) = (u as f64, v as f64);
+    let (sin_alpha, cos_alpha) = self.alpha.sin_cos();
+    let (sin_area, cos_area) = (f64::consts::PI + u * self.solid_angle).sin_cos();
+    let sin_phi = sin_area * cos_alpha - cos_area * sin_alpha;
+    let cos_phi = cos_area * cos_alpha + sin_area * sin_alpha;
+
+    let k1 = cos_phi + cos_alpha;
+    let k2 = sin_phi - sin_alpha * a.dot(b);
+    let cos_b =
+      ((k2 + (k2 * cos_phi - k1 * sin_phi) * cos_alpha) / ((k2 * sin_phi + k1 * cos_phi) * sin_alpha)).clamp(-1.0, 1.0);
+    let c_prime = a * cos_b + gram_schmidt(c, a) * (1.0 - cos_b * cos_b).max(0.0).sqrt();
+
+    let cos_theta = 1.0 - v * (1.0 - c_prime.dot(b));
+    let dir = b * cos_theta + gram_schmidt(&c_prime, b) * (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    dir.iter().all(|x| x.is_finite()).then(|| UnitVector::from_raw(dir.cast()))
+  }
+}
+
+impl TriangleSurface {
+  /// The triangle as seen from `point`, unless its solid angle is outside the range where sampling it works well.
+  fn spherical_triangle(&self, point: &WorldPoint) -> Option<SphericalTriangle> {
+    let [a, b, c] = [self.v0.0, self.v1.0, self.v2.0].map(|v| nalgebra::Vector3::from(v - *point).cast().normalize());
+    let [n_ab, n_bc, n_ca] = [a.cross(&b), b.cross(&c), c.cross(&a)];
+    if [n_ab, n_bc, n_ca].iter().any(|n| n.norm_squared() == 0.0) {
+      return None;
+    }
+
+    // The area of a spherical triangle is the amount by which its angles sum to more than pi
+    let [n_ab, n_bc, n_ca] = [n_ab, n_bc, n_ca].map(|n| n.normalize());
+    let alpha = angle_between(&n_ab, &-n_ca);
+    let solid_angle = alpha + angle_between(&n_bc, &-n_ab) + angle_between(&n_ca, &-n_bc) - f64::consts::PI;
+    (MIN_SPHERICAL_SAMPLE_AREA..=MAX_SPHERICAL_SAMPLE_AREA).contains(&solid_angle).then_some(SphericalTriangle {
+      a,
+      b,
+      c,
+      alpha,
+      solid_angle
+    })
+  }
+
+  fn area_pdf(&self, dist: Real, dir: &WorldUnitVector) -> Option<PositiveReal> {
+    area_to_solid_angle_pdf(1.0 / self.area, dist, dir.dot(&self.outer_normal))
+  }
+}
+
+impl ContinuousRandomVariable for TriangleSurface {
+  type Param = WorldPoint;
+  type Sample = WorldUnitVector;
+
+  fn sample_with_pdf(&self, point: &WorldPoint, sampler: &mut dyn Sampler) -> Option<(WorldUnitVector, PositiveReal)> {
+    let (u, v) = (sampler.next().into_inner(), sampler.next().into_inner());
+    if let Some(spherical) = self.spherical_triangle(point) {
+      return Some((spherical.sample(u, v)?, PositiveReal::new((1.0 / spherical.solid_angle) as Real)?));
+    }
+
+    // Otherwise sample a point uniformly by area, folding the unit square onto the triangle
+    let su = u.sqrt();
+    let p = self.v0.0 + self.edge1 * (su - v * su) + self.edge2 * (v * su);
+    let (dir, dist) = (p - *point).normalize_with_norm();
+    Some((dir, self.area_pdf(dist, &dir)?))
+  }
+
+  /// Like sampling, this ignores the alpha mask: directions through holes still reach whatever is behind them, which
+  /// the pdfs of the scene's emitters have to account for together.
+  fn pdf(&self, point: &WorldPoint, dir: &WorldUnitVector) -> Option<PositiveReal> {
+    let (dist, _) = self.locate_hit(&Ray::new(*point, *dir))?;
+    match self.spherical_triangle(point) {
+      Some(spherical) => PositiveReal::new((1.0 / spherical.solid_angle) as Real),
+      None => self.area_pdf(dist.into_inner(), dir)
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{
+    super::{alpha_mask::AlphaMaskParameters, testing::mismatched_pdf_fraction},
+    *
+  };
+  use crate::{lights::NullLight, materials::NullMaterial, sampling::IndependentSampler};
+
+  const STRATA: usize = 128;
+  const SAMPLES: usize = 10_000;
+
+  fn triangle(vertices: [[Real; 3]; 3]) -> TriangleSurface {
+    let visibility = serde_json::from_str::<VisibilityParameters>("{}").unwrap().build_visibility(None);
+    let vertices = vertices.map(|v| (WorldPoint::from_array(v), ObjectPoint::from_array(v)));
+    TriangleSurface::new(Arc::new(NullLight), Arc::new(NullMaterial), vertices, None, None, None, visibility)
+  }
+
+  /// Estimates the integral of the pdf of directions from `point`, all of which meet the triangle through a disk in its
+  /// plane. The disk is sampled by area on a jittered grid, which stays accurate when the triangle nearly surrounds the
+  /// point and its pdf is concentrated in grazing directions.
+  fn integrate_pdf(surface: &TriangleSurface, point: &WorldPoint, strata: usize) -> Real {
+    let mut sampler = IndependentSampler::seeded(1);
+    let corners = [surface.v0.0, surface.v1.0, surface.v2.0];
+    let center = corners[0] * (1.0 / 3.0) + (corners[1] * (1.0 / 3.0)).into() + (corners[2] * (1.0 / 3.0)).into();
+    let radius = 1.1 * corners.iter().map(|c| (*c - center).norm()).fold(0.0, Real::max);
+    let (t, b) = surface.outer_normal.orthonormal_basis();
+
+    let mut sum = 0.0;
+    for i in 0..strata {
+      for j in 0..strata {
+        let r = radius * ((i as Real + sampler.next().into_inner()) / strata as Real).sqrt();
+        let (sin_phi, cos_phi) = (2.0 * PI * (j as Real + sampler.next().into_inner()) / strata as Real).sin_cos();
+        let (dir, dist) = (center + t * (r * cos_phi) + b * (r * sin_phi) - *point).normalize_with_norm();
+        let pdf = surface.pdf(point, &dir).map_or(0.0, PositiveReal::into_inner);
+        sum += (pdf * dir.abs_dot(&surface.outer_normal) / (dist * dist)) as f64;
+      }
+    }
+
+    (sum / (strata * strata) as f64) as Real * PI * radius * radius
+  }
+
+  /// Triangles seen from a point, and whether they are sampled as spherical triangles (rather than by area) from it
+  fn cases() -> [([[Real; 3]; 3], [Real; 3], bool); 5] {
+    [
+      // Small enough that it is sampled by area
+      ([[-0.05, -0.05, 0.0], [0.05, -0.05, 0.0], [0.0, 0.05, 0.0]], [0.1, 0.2, 8.0], false),
+      // Small
+      ([[-0.05, -0.05, 0.0], [0.05, -0.05, 0.0], [0.0, 0.05, 0.0]], [0.0, 0.0, 3.0], true),
+      // Medium, seen from either side
+      ([[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]], [0.2, 0.3, 0.8], true),
+      ([[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]], [-0.5, 0.7, -0.4], true),
+      // Nearly enclosing the point, so that it is sampled by area
+      ([[-10.0, -10.0, 0.0], [10.0, -10.0, 0.0], [0.0, 10.0, 0.0]], [0.0, -1.0, 0.02], false)
+    ]
+  }
+
+  #[test]
+  fn sampling_method_depends_on_solid_angle() {
+    for (vertices, point, spherical) in cases() {
+      let solid_angle = triangle(vertices).spherical_triangle(&WorldPoint::from_array(point)).map(|s| s.solid_angle);
+      assert_eq!(solid_angle.is_some(), spherical, "triangle {vertices:?} seen from {point:?}");
+    }
+  }
+
+  #[test]
+  fn pdf_integrates_to_one() {
+    for (vertices, point, _) in cases() {
+      let point = WorldPoint::from_array(point);
+      let integral = integrate_pdf(&triangle(vertices), &point, STRATA);
+      assert!((integral - 1.0).abs() < 0.01, "pdf integrates to {integral} for {vertices:?} from {point:?}");
+    }
+  }
+
+  #[test]
+  fn sampled_pdfs_match_pdf() {
+    for (vertices, point, _) in cases() {
+      let point = WorldPoint::from_array(point);
+      let mismatched = mismatched_pdf_fraction(&triangle(vertices), &point, SAMPLES, 1e-3);
+      assert!(mismatched < 1e-3, "{mismatched} of the sampled pdfs for {vertices:?} from {point:?} differ");
+    }
+  }
+
+  #[test]
+  fn alpha_mask_leaves_pdf_unchanged() {
+    let mask =
+      serde_json::from_str::<AlphaMaskParameters>(r#"{"texture": {"type": "constant", "color": 1, "alpha": 0.5}}"#);
+    let mask = mask.unwrap().build_alpha_mask();
+    for (vertices, point, _) in cases() {
+      let (surface, point) =
+        (TriangleSurface { alpha_mask: Some(mask.clone()), ..triangle(vertices) }, WorldPoint::from_array(point));
+      let mismatched = mismatched_pdf_fraction(&surface, &point, SAMPLES, 1e-3);
+      assert!(mismatched < 1e-3, "{mismatched} of the sampled pdfs for {vertices:?} from {point:?} differ");
+    }
+  }
 }
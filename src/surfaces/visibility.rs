@@ -0,0 +1,73 @@
+use std::sync::Arc;
+
+use serde::Deserialize;
+
+use crate::raytracing::RayKind;
+
+fn default_visible() -> bool { true }
+
+/// Which rays can see a surface, and which lights illuminate it. Lights are referred to by name, where a surface's
+/// light is named by the surface and a light source by its optional `name`.
+#[derive(Debug, Deserialize)]
+pub struct VisibilityParameters {
+  #[serde(alias = "camera-visible", default = "default_visible")]
+  camera_visible: bool,
+
+  #[serde(alias = "casts-shadows", default = "default_visible")]
+  casts_shadows: bool,
+
+  #[serde(alias = "reflection-visible", default = "default_visible")]
+  reflection_visible: bool,
+
+  /// The only lights which illuminate the surface, if given
+  #[serde(alias = "light-include", default)]
+  light_include: Option<Vec<String>>,
+
+  #[serde(alias = "light-exclude", default)]
+  light_exclude: Vec<String>
+}
+
+impl VisibilityParameters {
+  /// Builds the visibility of a surface which emits light from the light named `light`, if any.
+  pub fn build_visibility(&self, light: Option<&String>) -> Arc<Visibility> {
+    Arc::new(Visibility {
+      camera_visible: self.camera_visible,
+      casts_shadows: self.casts_shadows,
+      reflection_visible: self.reflection_visible,
+      light_include: self.light_include.clone(),
+      light_exclude: self.light_exclude.clone(),
+      light: light.cloned()
+    })
+  }
+}
+
+#[derive(Debug)]
+pub struct Visibility {
+  camera_visible: bool,
+  casts_shadows: bool,
+  reflection_visible: bool,
+  light_include: Option<Vec<String>>,
+  light_exclude: Vec<String>,
+  light: Option<String>
+}
+
+impl Visibility {
+  pub fn is_visible_to(&self, kind: RayKind) -> bool {
+    match kind {
+      RayKind::Camera => self.camera_visible,
+      RayKind::Reflection => self.reflection_visible,
+      RayKind::Shadow => self.casts_shadows
+    }
+  }
+
+  /// Whether the light with the given name illuminates this surface, where unnamed lights illuminate everything.
+  pub fn is_lit_by(&self, light: Option<&str>) -> bool {
+    light.is_none_or(|name| {
+      self.light_include.as_ref().is_none_or(|include| include.iter().any(|l| l == name))
+        && !self.light_exclude.iter().any(|l| l == name)
+    })
+  }
+
+  /// The name of the light this surface emits, if any.
+  pub fn light(&self) -> Option<&str> { self.light.as_deref() }
+}
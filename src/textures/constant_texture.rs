@@ -2,28 +2,36 @@ use std::sync::Arc;
 
 use serde::Deserialize;
 
-use super::{Texture, TextureCoordinate, TextureParameters};
-use crate::spectrum::*;
+use super::{Texture, TextureCoordinate, TextureParameters, TexturePoint};
+use crate::{math::Real, spectrum::*};
+
+fn default_alpha() -> Real { 1.0 }
 
 #[derive(Debug, Clone, Copy, Deserialize)]
 pub struct ConstantTextureParameters {
-  color: ColorParameters
+  color: ColorParameters,
+
+  #[serde(default = "default_alpha")]
+  alpha: Real
 }
 
 #[typetag::deserialize(name = "constant")]
 impl TextureParameters for ConstantTextureParameters {
-  fn build_texture(&self) -> Arc<dyn Texture> { Arc::new(ConstantTexture::new(self.color.build_color())) }
+  fn build_texture(&self) -> Arc<dyn Texture> { Arc::new(ConstantTexture::new(self.color.build_color(), self.alpha)) }
 }
 
 #[derive(Debug)]
 pub struct ConstantTexture {
-  color: Spectrum
+  color: Spectrum,
+  alpha: Real
 }
 
 impl ConstantTexture {
-  pub fn new(color: Spectrum) -> Self { Self { color } }
+  pub fn new(color: Spectrum, alpha: Real) -> Self { Self { color, alpha } }
 }
 
 impl Texture for ConstantTexture {
-  fn value(&self, _: &TextureCoordinate) -> Spectrum { self.color }
+  fn value(&self, _: &TexturePoint) -> Spectrum { self.color }
+
+  fn alpha(&self, _: &TextureCoordinate) -> Real { self.alpha }
 }
@@ -1,39 +1,95 @@
 use std::sync::Arc;
 
-use image::{io::Reader, ImageBuffer, Rgb};
+use image::{io::Reader, Rgba};
 use serde::Deserialize;
 
-use super::{Texture, TextureCoordinate, TextureParameters};
+use super::{mipmap::*, Texture, TextureCoordinate, TextureParameters, TexturePoint};
 use crate::{math::Real, spectrum::*};
 
+/// How the color channels of an image are stored. Alpha is always linear.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+enum TextureEncoding {
+  /// Encoded with the sRGB transfer function and sRGB primaries, as most 8-bit color images are
+  #[serde(alias = "srgb")]
+  Srgb,
+
+  /// Used as they are, as the working color space or non-color data such as normals, heights or weights
+  #[default]
+  #[serde(alias = "linear")]
+  Linear
+}
+
+/// How an image is averaged over the area of a texture lookup.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+enum TextureFilter {
+  /// Interpolates the full resolution image, ignoring the size of the lookup
+  #[serde(alias = "bilinear")]
+  Bilinear,
+
+  /// Interpolates between the two mipmap levels closest in size to the lookup
+  #[default]
+  #[serde(alias = "trilinear")]
+  Trilinear,
+
+  /// Elliptically weighted averaging, which follows the shape of the lookup as well as its size
+  #[serde(alias = "ewa")]
+  Ewa
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct ImageTextureParameters {
-  filename: String
+  filename: String,
+
+  #[serde(default)]
+  encoding: TextureEncoding,
+
+  #[serde(default)]
+  wrap: WrapMode,
+
+  #[serde(default)]
+  filter: TextureFilter
 }
 
 #[typetag::deserialize(name = "image")]
 impl TextureParameters for ImageTextureParameters {
   fn build_texture(&self) -> Arc<dyn Texture> {
-    Arc::new(ImageTexture { image: Reader::open(&self.filename).unwrap().decode().unwrap().into_rgb8() })
+    let mut image = Reader::open(&self.filename).unwrap().decode().unwrap().into_rgba32f();
+    if let TextureEncoding::Srgb = self.encoding {
+      let working_space = working_space();
+      for pixel in image.pixels_mut() {
+        let encoded = Spectrum::new(pixel[0], pixel[1], pixel[2]);
+        let linear = Spectrum::new(srgb_decode(encoded.r()), srgb_decode(encoded.g()), srgb_decode(encoded.b()));
+        let color = ColorSpace::LinearSrgb.convert(&linear, working_space);
+        *pixel = Rgba([color.r(), color.g(), color.b(), pixel[3]]);
+      }
+    }
+
+    Arc::new(ImageTexture { mipmap: Mipmap::new(&image, self.wrap), filter: self.filter })
   }
 }
 
+/// An image whose color channels are stored in the working color space.
 #[derive(Debug)]
 pub struct ImageTexture {
-  image: ImageBuffer<Rgb<u8>, Vec<u8>>
+  mipmap: Mipmap,
+  filter: TextureFilter
 }
 
-impl ImageTexture {}
-
 impl Texture for ImageTexture {
-  // TODO: Bilinear interpolation
-  fn value(&self, uv: &TextureCoordinate) -> Spectrum {
-    let w = self.image.width();
-    let x = (uv[0] * (w as Real)).clamp(0.0, (w - 1) as Real) as u32;
-
-    let h = self.image.height();
-    let y = (uv[1] * (h as Real)).clamp(0.0, (h - 1) as Real) as u32;
+  fn value(&self, point: &TexturePoint) -> Spectrum {
+    let (uv, footprint) = (&point.tex_coord, &point.footprint);
+    let texel = match self.filter {
+      TextureFilter::Bilinear => self.mipmap.bilinear(0, uv),
+      TextureFilter::Trilinear => {
+        let (dx, dy) = (footprint.duv_dx, footprint.duv_dy);
+        let width = 2.0 * dx[0].abs().max(dx[1].abs()).max(dy[0].abs()).max(dy[1].abs());
+        self.mipmap.trilinear(uv, width)
+      },
+      TextureFilter::Ewa => self.mipmap.ewa(uv, &footprint.duv_dx, &footprint.duv_dy)
+    };
 
-    Spectrum::from_bytes(self.image.get_pixel(x, y).0)
+    Spectrum::new(texel[0], texel[1], texel[2])
   }
+
+  fn alpha(&self, uv: &TextureCoordinate) -> Real { self.mipmap.bilinear(0, uv)[3] }
 }
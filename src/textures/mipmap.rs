@@ -0,0 +1,205 @@
+use image::{ImageBuffer, Rgba};
+use nalgebra as na;
+use serde::Deserialize;
+
+use super::TextureCoordinate;
+use crate::math::Real;
+
+type Texel = na::Vector4<Real>;
+
+/// The longest an EWA filter's ellipse can be relative to its width, beyond which it's widened (blurring the texture)
+/// to bound the number of texels it covers.
+const MAX_ANISOTROPY: Real = 8.0;
+
+/// How quickly the Gaussian weights of an EWA filter fall off towards the edge of its ellipse.
+const EWA_ALPHA: Real = 2.0;
+
+/// How texture coordinates outside of [0, 1] are brought back onto the image.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub enum WrapMode {
+  #[default]
+  #[serde(alias = "repeat")]
+  Repeat,
+
+  #[serde(alias = "clamp")]
+  Clamp,
+
+  /// Repeats the image, flipping every other copy so that its edges meet
+  #[serde(alias = "mirror")]
+  Mirror
+}
+
+impl WrapMode {
+  fn wrap(self, i: i64, n: u32) -> usize {
+    let n = n as i64;
+    let i = match self {
+      WrapMode::Repeat => i.rem_euclid(n),
+      WrapMode::Clamp => i.clamp(0, n - 1),
+      WrapMode::Mirror => {
+        let i = i.rem_euclid(2 * n);
+        if i < n {
+          i
+        } else {
+          2 * n - 1 - i
+        }
+      }
+    };
+
+    i as usize
+  }
+}
+
+#[derive(Debug)]
+struct Level {
+  width: u32,
+  height: u32,
+  texels: Vec<Texel>
+}
+
+impl Level {
+  fn texel(&self, x: usize, y: usize) -> Texel { self.texels[y * self.width as usize + x] }
+
+  /// Halves the level in each direction (rounding up) by averaging blocks of 2x2 texels.
+  fn downsample(&self) -> Level {
+    let (width, height) = (self.width.div_ceil(2), self.height.div_ceil(2));
+    let mut texels = Vec::with_capacity((width * height) as usize);
+    for y in 0..height as usize {
+      for x in 0..width as usize {
+        let (x0, x1) = (2 * x, (2 * x + 1).min(self.width as usize - 1));
+        let (y0, y1) = (2 * y, (2 * y + 1).min(self.height as usize - 1));
+        texels.push((self.texel(x0, y0) + self.texel(x1, y0) + self.texel(x0, y1) + self.texel(x1, y1)) * 0.25);
+      }
+    }
+
+    Level { width, height, texels }
+  }
+}
+
+/// An image along with successively halved copies of it, down to a single texel, for looking up its average over
+/// areas of any size in roughly constant time.
+#[derive(Debug)]
+pub struct Mipmap {
+  levels: Vec<Level>,
+  wrap: WrapMode
+}
+
+impl Mipmap {
+  pub fn new(image: &ImageBuffer<Rgba<f32>, Vec<f32>>, wrap: WrapMode) -> Self {
+    let texels = image.pixels().map(|pixel| Texel::from(pixel.0)).collect();
+    let mut levels = vec![Level { width: image.width(), height: image.height(), texels }];
+    while let Some(level) = levels.last().filter(|level| level.width > 1 || level.height > 1) {
+      levels.push(level.downsample());
+    }
+
+    Self { levels, wrap }
+  }
+
+  fn resolution(&self) -> Real { self.levels[0].width.max(self.levels[0].height) as Real }
+
+  fn texel(&self, level: usize, x: i64, y: i64) -> Texel {
+    let level = &self.levels[level];
+    level.texel(self.wrap.wrap(x, level.width), self.wrap.wrap(y, level.height))
+  }
+
+  /// Interpolates between the four texels of a level nearest to `uv`, whose centres lie half a texel in from its
+  /// corners.
+  pub fn bilinear(&self, level: usize, uv: &TextureCoordinate) -> Texel {
+    let Level { width, height, .. } = self.levels[level];
+    let (x, y) = (uv[0] * width as Real - 0.5, uv[1] * height as Real - 0.5);
+    let (x0, y0) = (x.floor(), y.floor());
+    let (dx, dy) = (x - x0, y - y0);
+    let (x0, y0) = (x0 as i64, y0 as i64);
+
+    self.texel(level, x0, y0) * ((1.0 - dx) * (1.0 - dy))
+      + self.texel(level, x0 + 1, y0) * (dx * (1.0 - dy))
+      + self.texel(level, x0, y0 + 1) * ((1.0 - dx) * dy)
+      + self.texel(level, x0 + 1, y0 + 1) * (dx * dy)
+  }
+
+  /// Averages the image over a square of side `width` (in texture coordinates) around `uv`, by interpolating between
+  /// the two levels whose texels are closest to that size.
+  pub fn trilinear(&self, uv: &TextureCoordinate, width: Real) -> Texel {
+    let level = (width * self.resolution()).max(1e-8).log2();
+    if level <= 0.0 {
+      self.bilinear(0, uv)
+    } else if level >= (self.levels.len() - 1) as Real {
+      self.texel(self.levels.len() - 1, 0, 0)
+    } else {
+      let lower = level.floor() as usize;
+      let t = level - lower as Real;
+      self.bilinear(lower, uv) * (1.0 - t) + self.bilinear(lower + 1, uv) * t
+    }
+  }
+
+  /// Averages the image over the ellipse around `uv` with axes `axis_0` and `axis_1`, weighting texels with a Gaussian
+  /// so that anisotropic footprints (such as textures seen at grazing angles) aren't blurred along their short axis.
+  pub fn ewa(&self, uv: &TextureCoordinate, axis_0: &TextureCoordinate, axis_1: &TextureCoordinate) -> Texel {
+    let (mut major, mut minor) = ([axis_0[0], axis_0[1]], [axis_1[0], axis_1[1]]);
+    let length = |axis: &[Real; 2]| axis[0].hypot(axis[1]);
+    if length(&major) < length(&minor) {
+      std::mem::swap(&mut major, &mut minor);
+    }
+
+    let (major_length, mut minor_length) = (length(&major), length(&minor));
+    if minor_length > 0.0 && minor_length * MAX_ANISOTROPY < major_length {
+      let scale = major_length / (minor_length * MAX_ANISOTROPY);
+      minor = minor.map(|c| c * scale);
+      minor_length *= scale;
+    }
+
+    if minor_length == 0.0 {
+      return self.bilinear(0, uv);
+    }
+
+    let level = (minor_length * self.resolution()).log2().max(0.0);
+    let lower = level.floor() as usize;
+    let t = level - lower as Real;
+    self.ewa_level(lower, uv, &major, &minor) * (1.0 - t) + self.ewa_level(lower + 1, uv, &major, &minor) * t
+  }
+
+  fn ewa_level(&self, level: usize, uv: &TextureCoordinate, axis_0: &[Real; 2], axis_1: &[Real; 2]) -> Texel {
+    if level >= self.levels.len() {
+      return self.texel(self.levels.len() - 1, 0, 0);
+    }
+
+    // Work in texel coordinates, where the ellipse is given by a u² + b uv + c v² < 1
+    let Level { width, height, .. } = self.levels[level];
+    let (width, height) = (width as Real, height as Real);
+    let (x, y) = (uv[0] * width - 0.5, uv[1] * height - 0.5);
+    let (du_0, dv_0) = (axis_0[0] * width, axis_0[1] * height);
+    let (du_1, dv_1) = (axis_1[0] * width, axis_1[1] * height);
+
+    // Adding one to a and c makes sure the ellipse covers at least one texel
+    let mut a = dv_0 * dv_0 + dv_1 * dv_1 + 1.0;
+    let mut b = -2.0 * (du_0 * dv_0 + du_1 * dv_1);
+    let mut c = du_0 * du_0 + du_1 * du_1 + 1.0;
+    let inv_f = 1.0 / (a * c - b * b * 0.25);
+    a *= inv_f;
+    b *= inv_f;
+    c *= inv_f;
+
+    let det = 4.0 * a * c - b * b;
+    let (half_width, half_height) = (2.0 * (det * c).sqrt() / det, 2.0 * (det * a).sqrt() / det);
+
+    let mut sum = Texel::zeros();
+    let mut total_weight = 0.0;
+    for ty in (y - half_height).ceil() as i64..=(y + half_height).floor() as i64 {
+      let v = ty as Real - y;
+      for tx in (x - half_width).ceil() as i64..=(x + half_width).floor() as i64 {
+        let u = tx as Real - x;
+        let r2 = a * u * u + b * u * v + c * v * v;
+        if r2 < 1.0 {
+          let weight = (-EWA_ALPHA * r2).exp() - (-EWA_ALPHA).exp();
+          sum += self.texel(level, tx, ty) * weight;
+          total_weight += weight;
+        }
+      }
+    }
+
+    if total_weight > 0.0 {
+      sum / total_weight
+    } else {
+      self.bilinear(level, uv)
+    }
+  }
+}
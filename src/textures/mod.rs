@@ -1,5 +1,9 @@
 mod constant_texture;
 mod image_texture;
+mod mipmap;
+mod noise;
+mod noise_texture;
 mod texture;
 
+pub use constant_texture::*;
 pub use texture::*;
@@ -0,0 +1,118 @@
+use crate::math::Real;
+
+/// Hashes a lattice cell (and a seed, for drawing several values per cell) by mixing in each coordinate in turn with
+/// the MurmurHash3 finalizer.
+fn hash(cell: [i32; 3], seed: u64) -> u64 {
+  let mut h = seed.wrapping_mul(0x9e3779b97f4a7c15);
+  for c in cell {
+    h ^= c as u32 as u64;
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xff51afd7ed558ccd);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xc4ceb9fe1a85ec53);
+    h ^= h >> 33;
+  }
+
+  h
+}
+
+/// The dot product of an offset from a lattice point with one of the twelve gradients pointing to the edges of a
+/// cube, chosen by `hash`.
+fn gradient(hash: u64, [x, y, z]: [Real; 3]) -> Real {
+  let h = hash & 15;
+  let u = if h < 8 { x } else { y };
+  let v = if h < 4 {
+    y
+  } else if h == 12 || h == 14 {
+    x
+  } else {
+    z
+  };
+
+  (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+}
+
+/// The quintic which blends between lattice points, so that the noise has continuous second derivatives.
+fn fade(t: Real) -> Real { t * t * t * (t * (t * 6.0 - 15.0) + 10.0) }
+
+fn lerp(t: Real, a: Real, b: Real) -> Real { a + t * (b - a) }
+
+/// Ken Perlin's improved gradient noise, which is zero on the integer lattice and varies smoothly within about [-1, 1]
+/// in between.
+pub fn perlin(p: [Real; 3]) -> Real {
+  let cell = p.map(|c| c.floor() as i32);
+  let [dx, dy, dz] = [0, 1, 2].map(|i| p[i] - p[i].floor());
+  let [u, v, w] = [dx, dy, dz].map(fade);
+
+  let corner = |i: i32, j: i32, k: i32| {
+    let h = hash([cell[0].wrapping_add(i), cell[1].wrapping_add(j), cell[2].wrapping_add(k)], 0);
+    gradient(h, [dx - i as Real, dy - j as Real, dz - k as Real])
+  };
+
+  lerp(
+    w,
+    lerp(v, lerp(u, corner(0, 0, 0), corner(1, 0, 0)), lerp(u, corner(0, 1, 0), corner(1, 1, 0))),
+    lerp(v, lerp(u, corner(0, 0, 1), corner(1, 0, 1)), lerp(u, corner(0, 1, 1), corner(1, 1, 1)))
+  )
+}
+
+/// How octaves of noise are layered, each `lacunarity` times the frequency and `gain` times the amplitude of the last.
+#[derive(Debug, Clone, Copy)]
+pub struct Octaves {
+  pub count: usize,
+  pub lacunarity: Real,
+  pub gain: Real
+}
+
+impl Octaves {
+  /// Sums `noise` over the octaves, normalized by the total amplitude.
+  fn sum(&self, p: [Real; 3], noise: impl Fn([Real; 3]) -> Real) -> Real {
+    let (mut frequency, mut amplitude) = (1.0, 1.0);
+    let (mut sum, mut total_amplitude) = (0.0, 0.0);
+    for _ in 0..self.count {
+      sum += noise(p.map(|c| c * frequency)) * amplitude;
+      total_amplitude += amplitude;
+      frequency *= self.lacunarity;
+      amplitude *= self.gain;
+    }
+
+    if total_amplitude > 0.0 {
+      sum / total_amplitude
+    } else {
+      0.0
+    }
+  }
+}
+
+/// Fractional Brownian motion, which adds detail to Perlin noise at finer scales while staying within [-1, 1].
+pub fn fbm(p: [Real; 3], octaves: &Octaves) -> Real { octaves.sum(p, perlin) }
+
+/// Like fractional Brownian motion but summing the absolute value of each octave, which gives creases where the noise
+/// crosses zero and values within [0, 1].
+pub fn turbulence(p: [Real; 3], octaves: &Octaves) -> Real { octaves.sum(p, |p| perlin(p).abs()) }
+
+/// The distances from `p` to the nearest and second nearest of a set of points scattered one to each lattice cell.
+pub fn worley(p: [Real; 3]) -> (Real, Real) {
+  let cell = p.map(|c| c.floor() as i32);
+  let (mut nearest, mut second) = (Real::INFINITY, Real::INFINITY);
+  for i in -1..=1 {
+    for j in -1..=1 {
+      for k in -1..=1 {
+        let neighbour = [cell[0].wrapping_add(i), cell[1].wrapping_add(j), cell[2].wrapping_add(k)];
+        let offset = [0, 1, 2].map(|axis| {
+          let jitter = (hash(neighbour, axis + 1) >> 40) as Real / (1u64 << 24) as Real;
+          neighbour[axis as usize] as Real + jitter - p[axis as usize]
+        });
+
+        let dist = (offset[0] * offset[0] + offset[1] * offset[1] + offset[2] * offset[2]).sqrt();
+        if dist < nearest {
+          (nearest, second) = (dist, nearest);
+        } else if dist < second {
+          second = dist;
+        }
+      }
+    }
+  }
+
+  (nearest, second)
+}
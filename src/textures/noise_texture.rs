@@ -0,0 +1,244 @@
+use std::sync::Arc;
+
+use serde::Deserialize;
+
+use super::{noise::*, Texture, TextureParameters, TexturePoint};
+use crate::{
+  math::{Real, PI},
+  spectrum::*
+};
+
+fn default_frequency() -> Real { 1.0 }
+
+fn default_low() -> ColorParameters { ColorParameters::Single(0.0) }
+
+fn default_high() -> ColorParameters { ColorParameters::Single(1.0) }
+
+fn default_octaves() -> usize { 6 }
+
+fn default_lacunarity() -> Real { 2.0 }
+
+fn default_gain() -> Real { 0.5 }
+
+fn default_marble_variation() -> Real { 2.0 }
+
+fn default_wood_variation() -> Real { 0.2 }
+
+/// How a pattern is placed and colored, shared by all of the noise textures.
+#[derive(Debug, Clone, Copy, Deserialize)]
+struct PatternParameters {
+  /// How many times the pattern repeats per unit of object space
+  #[serde(default = "default_frequency")]
+  frequency: Real,
+
+  /// The colors the pattern blends between as it goes from zero to one
+  #[serde(default = "default_low")]
+  low: ColorParameters,
+
+  #[serde(default = "default_high")]
+  high: ColorParameters
+}
+
+impl PatternParameters {
+  fn build_noise_texture(&self, pattern: Pattern) -> Arc<dyn Texture> {
+    Arc::new(NoiseTexture {
+      pattern,
+      frequency: self.frequency,
+      low: self.low.build_color(),
+      high: self.high.build_color()
+    })
+  }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+struct OctaveParameters {
+  #[serde(default = "default_octaves")]
+  octaves: usize,
+
+  #[serde(default = "default_lacunarity")]
+  lacunarity: Real,
+
+  #[serde(default = "default_gain")]
+  gain: Real
+}
+
+impl OctaveParameters {
+  fn build_octaves(&self) -> Octaves { Octaves { count: self.octaves, lacunarity: self.lacunarity, gain: self.gain } }
+}
+
+#[derive(Debug, Deserialize)]
+struct PerlinParameters {
+  #[serde(flatten)]
+  pattern: PatternParameters
+}
+
+#[typetag::deserialize(name = "perlin")]
+impl TextureParameters for PerlinParameters {
+  fn build_texture(&self) -> Arc<dyn Texture> { self.pattern.build_noise_texture(Pattern::Perlin) }
+}
+
+#[derive(Debug, Deserialize)]
+struct FbmParameters {
+  #[serde(flatten)]
+  pattern: PatternParameters,
+
+  #[serde(flatten)]
+  octaves: OctaveParameters
+}
+
+#[typetag::deserialize(name = "fbm")]
+impl TextureParameters for FbmParameters {
+  fn build_texture(&self) -> Arc<dyn Texture> {
+    self.pattern.build_noise_texture(Pattern::Fbm(self.octaves.build_octaves()))
+  }
+}
+
+#[derive(Debug, Deserialize)]
+struct TurbulenceParameters {
+  #[serde(flatten)]
+  pattern: PatternParameters,
+
+  #[serde(flatten)]
+  octaves: OctaveParameters
+}
+
+#[typetag::deserialize(name = "turbulence")]
+impl TextureParameters for TurbulenceParameters {
+  fn build_texture(&self) -> Arc<dyn Texture> {
+    self.pattern.build_noise_texture(Pattern::Turbulence(self.octaves.build_octaves()))
+  }
+}
+
+/// Which distances to the scattered points of cellular noise make up the pattern.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+enum WorleyFeature {
+  /// The distance to the nearest point, which gives round cells
+  #[default]
+  #[serde(alias = "f1")]
+  F1,
+
+  /// The distance to the second nearest point
+  #[serde(alias = "f2")]
+  F2,
+
+  /// The difference between the two, which is zero along the borders between cells
+  #[serde(alias = "f2-f1")]
+  F2MinusF1
+}
+
+#[derive(Debug, Deserialize)]
+struct WorleyParameters {
+  #[serde(flatten)]
+  pattern: PatternParameters,
+
+  #[serde(default)]
+  feature: WorleyFeature
+}
+
+#[typetag::deserialize(name = "worley")]
+impl TextureParameters for WorleyParameters {
+  fn build_texture(&self) -> Arc<dyn Texture> { self.pattern.build_noise_texture(Pattern::Worley(self.feature)) }
+}
+
+#[derive(Debug, Deserialize)]
+struct MarbleParameters {
+  #[serde(flatten)]
+  pattern: PatternParameters,
+
+  #[serde(flatten)]
+  octaves: OctaveParameters,
+
+  /// How far turbulence pushes the veins around
+  #[serde(default = "default_marble_variation")]
+  variation: Real
+}
+
+#[typetag::deserialize(name = "marble")]
+impl TextureParameters for MarbleParameters {
+  fn build_texture(&self) -> Arc<dyn Texture> {
+    self
+      .pattern
+      .build_noise_texture(Pattern::Marble { octaves: self.octaves.build_octaves(), variation: self.variation })
+  }
+}
+
+#[derive(Debug, Deserialize)]
+struct WoodParameters {
+  #[serde(flatten)]
+  pattern: PatternParameters,
+
+  #[serde(flatten)]
+  octaves: OctaveParameters,
+
+  /// How far noise warps the rings
+  #[serde(default = "default_wood_variation")]
+  variation: Real
+}
+
+#[typetag::deserialize(name = "wood")]
+impl TextureParameters for WoodParameters {
+  fn build_texture(&self) -> Arc<dyn Texture> {
+    self.pattern.build_noise_texture(Pattern::Wood { octaves: self.octaves.build_octaves(), variation: self.variation })
+  }
+}
+
+#[derive(Debug)]
+enum Pattern {
+  Perlin,
+  Fbm(Octaves),
+  Turbulence(Octaves),
+  Worley(WorleyFeature),
+
+  /// Veins running across the x-axis, bent by turbulence
+  Marble {
+    octaves: Octaves,
+    variation: Real
+  },
+
+  /// Rings around the z-axis, warped by fractional Brownian motion
+  Wood {
+    octaves: Octaves,
+    variation: Real
+  }
+}
+
+impl Pattern {
+  /// The pattern at `p`, which is roughly within [0, 1].
+  fn value(&self, p: [Real; 3]) -> Real {
+    match self {
+      Pattern::Perlin => 0.5 * (perlin(p) + 1.0),
+      Pattern::Fbm(octaves) => 0.5 * (fbm(p, octaves) + 1.0),
+      Pattern::Turbulence(octaves) => turbulence(p, octaves),
+      Pattern::Worley(feature) => {
+        let (nearest, second) = worley(p);
+        match feature {
+          WorleyFeature::F1 => nearest,
+          WorleyFeature::F2 => second,
+          WorleyFeature::F2MinusF1 => second - nearest
+        }
+      },
+      Pattern::Marble { octaves, variation } => 0.5 + 0.5 * (PI * (p[0] + variation * turbulence(p, octaves))).sin(),
+      Pattern::Wood { octaves, variation } => {
+        let radius = p[0].hypot(p[1]) + variation * fbm(p, octaves);
+        0.5 - 0.5 * (2.0 * PI * radius).cos()
+      }
+    }
+  }
+}
+
+/// A solid texture which blends between two colors following a pattern of noise in object space.
+#[derive(Debug)]
+pub struct NoiseTexture {
+  pattern: Pattern,
+  frequency: Real,
+  low: Spectrum,
+  high: Spectrum
+}
+
+impl Texture for NoiseTexture {
+  fn value(&self, point: &TexturePoint) -> Spectrum {
+    let p = point.object_point;
+    let t = self.pattern.value([p[0], p[1], p[2]].map(|c| c * self.frequency)).clamp(0.0, 1.0);
+    self.low * (1.0 - t) + self.high * t
+  }
+}
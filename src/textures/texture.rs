@@ -1,6 +1,6 @@
 use std::{fmt::Debug, sync::Arc};
 
-use crate::{math::*, spectrum::*};
+use crate::{math::*, raytracing::ObjectPoint, spectrum::*};
 
 #[typetag::deserialize(tag = "type")]
 pub trait TextureParameters: Debug {
@@ -14,6 +14,31 @@ impl Space<2> for TextureSpace {}
 
 pub type TextureCoordinate = Vector<2, TextureSpace>;
 
+/// How far the texture coordinates move between neighbouring pixels, in screen x and y.
+#[derive(Debug, Clone, Copy)]
+pub struct TextureFootprint {
+  pub duv_dx: TextureCoordinate,
+  pub duv_dy: TextureCoordinate
+}
+
+impl TextureFootprint {
+  /// A footprint covering a single point, which filtered textures treat as their sharpest lookup.
+  pub fn point() -> Self { Self { duv_dx: TextureCoordinate::zero(), duv_dy: TextureCoordinate::zero() } }
+}
+
+/// Where a texture is looked up, both on the surface and in the object it belongs to, along with the area around that
+/// point which a pixel covers.
+#[derive(Debug, Clone, Copy)]
+pub struct TexturePoint {
+  pub tex_coord: TextureCoordinate,
+  pub object_point: ObjectPoint,
+  pub footprint: TextureFootprint
+}
+
 pub trait Texture: Debug {
-  fn value(&self, tex_coord: &TextureCoordinate) -> Spectrum;
+  /// The texture at `point`, which textures that can be filtered average over its footprint.
+  fn value(&self, point: &TexturePoint) -> Spectrum;
+
+  /// The opacity of the texture, which is fully opaque unless the texture says otherwise.
+  fn alpha(&self, _: &TextureCoordinate) -> Real { 1.0 }
 }